@@ -0,0 +1,229 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::state::{SimTime, WireState};
+
+/// Wire recorded by [`LogicAnalyzer`]
+pub struct Probe {
+    pub wire: usize,
+    pub name: String,
+    pub width: u32,
+
+    /// State changes, oldest first
+    pub changes: VecDeque<(SimTime, WireState)>,
+}
+
+impl Probe {
+    /// State at given time, None if it's before recorded changes
+    pub fn state_at(&self, time: SimTime) -> Option<WireState> {
+        let index = self.changes.partition_point(|(t, _)| *t <= time);
+        index.checked_sub(1).map(|i| self.changes[i].1)
+    }
+}
+
+/// Records wire state changes over simulation time
+#[derive(Default)]
+pub struct LogicAnalyzer {
+    pub probes: Vec<Probe>,
+    pub recording: bool,
+}
+
+impl LogicAnalyzer {
+    /// Changes kept per probe, older ones are dropped
+    pub const MAX_CHANGES: usize = 65536;
+
+    pub fn add_probe(
+        &mut self,
+        wire: usize,
+        name: String,
+        width: u32,
+        time: SimTime,
+        state: WireState,
+    ) {
+        if self.probes.iter().any(|p| p.wire == wire) {
+            return;
+        }
+        self.probes.push(Probe {
+            wire,
+            name,
+            width,
+            changes: VecDeque::from([(time, state)]),
+        });
+    }
+
+    pub fn remove_probe(&mut self, index: usize) {
+        if index < self.probes.len() {
+            self.probes.remove(index);
+        }
+    }
+
+    /// Forget recorded changes, keeping probes
+    pub fn clear(&mut self) {
+        for probe in self.probes.iter_mut() {
+            probe.changes.clear();
+        }
+    }
+
+    /// Forget changes after given time
+    pub fn truncate(&mut self, time: SimTime) {
+        for probe in self.probes.iter_mut() {
+            while probe.changes.back().is_some_and(|(t, _)| *t > time) {
+                probe.changes.pop_back();
+            }
+        }
+    }
+
+    pub fn record(&mut self, wire: usize, time: SimTime, state: WireState) {
+        if !self.recording {
+            return;
+        }
+        for probe in self.probes.iter_mut().filter(|p| p.wire == wire) {
+            if probe.changes.back().is_some_and(|(_, s)| *s == state) {
+                continue;
+            }
+            if probe.changes.len() >= Self::MAX_CHANGES {
+                probe.changes.pop_front();
+            }
+            probe.changes.push_back((time, state));
+        }
+    }
+
+    /// Earliest recorded time
+    pub fn start_time(&self) -> Option<SimTime> {
+        self.probes
+            .iter()
+            .filter_map(|p| p.changes.front().map(|(t, _)| *t))
+            .min()
+    }
+
+    /// Writes recorded changes in Value Change Dump format, with 1ns timescale.
+    /// `end` is the last timestamp, usually current simulation time
+    pub fn write_vcd(&self, out: &mut impl fmt::Write, end: SimTime) -> fmt::Result {
+        writeln!(out, "$version rls $end")?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module rls $end")?;
+        for (i, probe) in self.probes.iter().enumerate() {
+            let name: String = probe
+                .name
+                .chars()
+                .map(|c| if c.is_whitespace() { '_' } else { c })
+                .collect();
+            writeln!(out, "$var wire {} {} {name} $end", probe.width, vcd_id(i))?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+
+        let mut changes: Vec<_> = self
+            .probes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| p.changes.iter().map(move |(t, s)| (*t, i, *s)))
+            .collect();
+        // Stable, so changes at the same time keep their order
+        changes.sort_by_key(|(time, _, _)| *time);
+
+        let mut last_time = None;
+        for (time, i, state) in changes {
+            if last_time != Some(time) {
+                writeln!(out, "#{time}")?;
+                last_time = Some(time);
+            }
+            let width = self.probes[i].width;
+            if width == 1 {
+                writeln!(out, "{}{}", bit_char(state.bit(0)), vcd_id(i))?;
+            } else {
+                let bits: String = (0..width).rev().map(|b| bit_char(state.bit(b))).collect();
+                writeln!(out, "b{bits} {}", vcd_id(i))?;
+            }
+        }
+        if last_time.is_none_or(|t| t < end) {
+            writeln!(out, "#{end}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Hex value if all bits are defined, bits from the most significant one otherwise
+pub fn value_text(state: WireState, width: u32) -> String {
+    let mut value = 0u32;
+    for bit in 0..width {
+        match state.bit(bit) {
+            WireState::True => value |= 1 << bit,
+            WireState::False => {}
+            _ => return (0..width).rev().map(|b| bit_char(state.bit(b))).collect(),
+        }
+    }
+    format!("{value:#x}")
+}
+
+fn bit_char(state: WireState) -> char {
+    match state {
+        WireState::None => 'z',
+        WireState::True => '1',
+        WireState::False => '0',
+        WireState::Error | WireState::Bus(_) => 'x',
+    }
+}
+
+/// Identifier made of printable ASCII characters, as VCD requires
+fn vcd_id(mut index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+
+    let mut id = String::new();
+    loop {
+        id.push((FIRST + (index % COUNT) as u8) as char);
+        index /= COUNT;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vcd_export() {
+        let mut analyzer = LogicAnalyzer {
+            recording: true,
+            ..Default::default()
+        };
+        analyzer.add_probe(0, "clk".into(), 1, 0, WireState::False);
+        analyzer.add_probe(1, "data bus".into(), 4, 0, WireState::None);
+
+        analyzer.record(0, 5, WireState::True);
+        analyzer.record(0, 5, WireState::True);
+        let bits = [WireState::True, WireState::False, WireState::Error];
+        analyzer.record(1, 5, WireState::from_bits(4, bits));
+        analyzer.record(0, 10, WireState::False);
+
+        let mut vcd = String::new();
+        analyzer.write_vcd(&mut vcd, 12).unwrap();
+        assert_eq!(
+            vcd,
+            "$version rls $end\n\
+             $timescale 1ns $end\n\
+             $scope module rls $end\n\
+             $var wire 1 ! clk $end\n\
+             $var wire 4 \" data_bus $end\n\
+             $upscope $end\n\
+             $enddefinitions $end\n\
+             #0\n\
+             0!\n\
+             bzzzz \"\n\
+             #5\n\
+             1!\n\
+             bzx01 \"\n\
+             #10\n\
+             0!\n\
+             #12\n"
+        );
+
+        assert_eq!(analyzer.probes[0].state_at(7), Some(WireState::True));
+        assert_eq!(value_text(WireState::from_bits(4, bits), 4), "zx01");
+        assert_eq!(value_text(WireState::from_bits(8, bits[..2].to_vec()), 2), "0x1");
+        assert_eq!(vcd_id(94), "!!");
+    }
+}
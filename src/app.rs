@@ -2,25 +2,31 @@ use std::{collections::HashMap, fmt::Write, ops::Deref, sync::Arc};
 
 use eframe::{
     egui::{
-        self, CollapsingHeader, Context, FontSelection, Frame, Key, Margin, Sense, SidePanel,
-        TextStyle, Ui, WidgetText,
+        self, CollapsingHeader, ComboBox, Context, DragValue, FontSelection, Frame, Key, Layout,
+        Margin, RichText, ScrollArea, Sense, SidePanel, Slider, TextStyle, Ui, WidgetText,
     },
-    epaint::{Color32, Rounding, Stroke, TextShape},
+    epaint::{Color32, FontId, Rounding, Stroke, TextShape},
     CreationContext,
 };
-use emath::{pos2, vec2, Pos2, Rect, Vec2};
+use emath::{pos2, vec2, Align, Align2, Pos2, Rect, Vec2};
 
 use crate::{
-    board::{ActiveCircuitBoard, CircuitBoard, SelectedItem},
-    circuits::{self, props::CircuitPropertyImpl, CircuitPreview, CircuitPreviewImpl},
+    board::{selection::SelectedWorldObject, ActiveCircuitBoard, CircuitBoard, SelectedItem},
+    breakpoint::{Breakpoint, BreakpointCondition, BreakpointTarget, HitSource},
+    circuits::{self, props::CircuitPropertyImpl, CircuitPreview},
+    containers::SeededState,
+    state::{
+        ClockMode, Diagnostic, SimTime, SimulationStats, SimulationStep, State, UpdateTask,
+        WireState,
+    },
     time::Instant,
     ui::{
         CollapsibleSidePanel, Inventory, InventoryItem, InventoryItemGroup, PropertyEditor,
         PropertyStoreItem,
     },
-    vector::{Vec2f, Vector},
-    BasicLoadingContext, Direction4, DynStaticStr, PaintContext, PanAndZoom, PastePreview, RwLock,
-    TileDrawBounds,
+    vector::{Vec2f, Vec2i, Vector},
+    ArcString, BasicLoadingContext, Direction4, DynStaticStr, PaintContext, PanAndZoom,
+    PastePreview, RwLock, TileDrawBounds,
 };
 
 pub struct App {
@@ -40,6 +46,36 @@ pub struct App {
     circuit_previews: HashMap<DynStaticStr, Arc<CircuitPreview>>,
 
     props_ui: crate::ui::PropertyEditor,
+
+    step_ticks: SimTime,
+    last_step: Option<UpdateTask>,
+    /// Last used scale, so it won't reset when switching clock modes
+    clock_scale: f32,
+
+    show_analyzer: bool,
+    /// Nanoseconds of simulation time per pixel of timing diagram
+    analyzer_zoom: f32,
+    #[cfg(not(feature = "wasm"))]
+    vcd_path: String,
+    vcd_status: Option<String>,
+
+    show_states: bool,
+    /// State compared with the active one in states panel
+    compare_state: Option<usize>,
+    compare_differences_only: bool,
+
+    show_conflicts: bool,
+    show_breakpoints: bool,
+
+    show_profiler: bool,
+    profiler_sort: ProfilerColumn,
+    profiler_descending: bool,
+
+    /// Stats at previous frame, to measure simulation time per frame
+    last_stats: SimulationStats,
+    /// Start of updates per second measurement and task count at it
+    rate_sample: (Instant, u64),
+    updates_per_second: f32,
 }
 
 // TODO: fix coi sometimes not working by re-registering it and reloading
@@ -106,14 +142,53 @@ impl eframe::App for App {
                 self.debug = !self.debug;
             } else if ctx.input(|input| input.key_pressed(Key::F8)) {
                 let board = self.board.board.clone();
-                self.board = ActiveCircuitBoard::new(board, 0).unwrap();
+                let state_id = self.board.state_id;
+                let heat_map = self.board.heat_map;
+                self.board = ActiveCircuitBoard::new(board, state_id).unwrap();
+                self.board.heat_map = heat_map;
             } else if ctx.input(|input| input.key_pressed(Key::F4)) {
                 let state = &self.board.state;
                 state.reset();
                 state.update_everything();
+            } else if ctx.input(|input| input.key_pressed(Key::F5)) {
+                let state = &self.board.state;
+                state.set_paused(!state.is_paused());
+            } else if ctx.input(|input| input.key_pressed(Key::F6)) {
+                self.step_simulation(SimulationStep::Task);
+            } else if ctx.input(|input| input.key_pressed(Key::F7)) {
+                self.step_simulation(SimulationStep::Settle);
+            } else if ctx.input(|input| input.key_pressed(Key::F3)) {
+                self.step_simulation(SimulationStep::Ticks(self.step_ticks));
+            } else if ctx.input(|input| input.key_pressed(Key::F2)) {
+                self.show_analyzer = !self.show_analyzer;
+            } else if ctx.input(|input| input.key_pressed(Key::F10)) {
+                self.show_states = !self.show_states;
+            } else if ctx.input(|input| input.key_pressed(Key::F11)) {
+                self.show_conflicts = !self.show_conflicts;
+            } else if ctx.input(|input| input.key_pressed(Key::F12)) {
+                self.show_breakpoints = !self.show_breakpoints;
             }
         }
 
+        if self.show_analyzer {
+            self.analyzer_ui(ctx);
+        }
+        if self.show_states {
+            self.states_ui(ctx);
+        }
+        if self.show_conflicts {
+            self.conflicts_ui(ctx);
+        }
+        if self.show_breakpoints {
+            self.breakpoints_ui(ctx);
+        }
+        if self.show_profiler {
+            self.profiler_ui(ctx);
+        }
+        self.board
+            .state
+            .set_profiling(self.show_profiler || self.board.heat_map);
+
         egui::CentralPanel::default()
             .frame(egui::Frame::central_panel(ctx.style().as_ref()).inner_margin(Margin::same(0.0)))
             .show(ctx, |ui| {
@@ -123,9 +198,10 @@ impl eframe::App for App {
 
                 if let SelectedItem::Circuit(p) = self.selected_item() {
                     let props = [((), &p.props).into()];
-                    let changed = App::properties_ui(&mut self.props_ui, ui, Some(props))
-                        .is_some_and(|v| !v.is_empty());
-                    if changed {}
+                    let changes = App::properties_ui(&mut self.props_ui, ui, Some(props));
+                    for property in changes.into_iter().flatten() {
+                        p.prop_changed(&property.id);
+                    }
                 } else {
                     let selection = self.board.selection.borrow();
                     if !selection.selection.is_empty() {
@@ -240,6 +316,8 @@ impl eframe::App for App {
                         });
                     }
 
+                    self.simulation_controls_ui(&mut ui);
+
                     let mut text = String::new();
 
                     #[cfg(feature = "single_thread")]
@@ -250,22 +328,68 @@ impl eframe::App for App {
                     }
 
                     let paint_time = (Instant::now() - start_time).as_secs_f32() * 1000.0;
+                    let busy_time = self.measure_stats().as_secs_f32() * 1000.0;
+                    let updates_per_second = self.updates_per_second;
+                    let queue_len = self.board.state.queue_len();
+                    let sim_units = self.board.state.sim_time();
+                    let paused = self.board.state.is_paused();
+                    let step_ticks = self.step_ticks;
                     let debug = self.debug;
                     let ordered_queue = self.board.board.read().is_ordered_queue();
+                    let seed = self.board.state.seed();
+                    let state_name = {
+                        let board = self.board.board.read();
+                        board.states.state_name(self.board.state_id).unwrap_or_default()
+                    };
 
                     text.write_fmt(format_args!(
                         "Paint time: {paint_time:.02}ms\n\
+                         Sim busy: {busy_time:.02}ms\n\
+                         Updates/s: {updates_per_second:.0}\n\
+                         Queue: {queue_len}\n\
+                         Sim time: {sim_units} ns\n\
                          [F9] Debug: {debug}\n\
                          [F8] Board reload\n\
                          [F4] State reset\n\
+                         [F5] Paused: {paused}\n\
+                         [F6] Step\n\
+                         [F7] Step until settled\n\
+                         [F3] Step {step_ticks} ticks\n\
+                         [F2] Logic analyzer\n\
+                         [F10] States: {state_name}\n\
+                         [F11] Conflicts\n\
+                         [F12] Breakpoints\n\
                          [R]  Rotate\n\
                          [F]  Flip\n\
                          [Q]  Ordered queue: {ordered_queue}\n\
+                         Queue seed: {seed}\n\
                         "
                     ))
                     .unwrap();
 
+                    if let Some(task) = &self.last_step {
+                        text.write_fmt(format_args!("Last step: {task:?}\n")).unwrap();
+                    }
+                    if let Some(hit) = self.board.state.breakpoint_hit() {
+                        let target = {
+                            let board = self.board.board.read();
+                            Self::breakpoint_target_text(&board, &hit.breakpoint.target)
+                        };
+                        text.write_fmt(format_args!(
+                            "Breakpoint: {target} {} -> {} at {} ns\n",
+                            hit.old, hit.new, hit.time
+                        ))
+                        .unwrap();
+                        match hit.source {
+                            HitSource::Task(task) => {
+                                text.write_fmt(format_args!("Triggered by: {task:?}\n")).unwrap()
+                            }
+                            HitSource::External => text.write_str("Triggered by: input\n").unwrap(),
+                        }
+                    }
+
                     ui.monospace(text);
+                    self.diagnostics_ui(&mut ui);
                 }
             });
     }
@@ -281,13 +405,49 @@ impl eframe::App for App {
                 .filter_map(|(ty, p)| p.save().map(|d| (ty.clone(), d))),
         ));
         _storage.set_string("previews", ron::to_string(&previews).unwrap());
+        _storage.set_string("update_limit", State::update_limit().to_string());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProfilerColumn {
+    Kind,
+    Id,
+    Name,
+    Count,
+}
+
+impl ProfilerColumn {
+    const ALL: [ProfilerColumn; 4] = [Self::Kind, Self::Id, Self::Name, Self::Count];
+
+    fn name(self) -> &'static str {
+        match self {
+            ProfilerColumn::Kind => "Kind",
+            ProfilerColumn::Id => "Id",
+            ProfilerColumn::Name => "Name",
+            ProfilerColumn::Count => "Count",
+        }
     }
 }
 
+/// Circuit updates or wire changes shown in profiler
+struct ProfilerRow {
+    kind: &'static str,
+    id: usize,
+    name: String,
+    count: u64,
+    /// Where to pan when clicked
+    pos: Vec2i,
+}
+
 static INVENTORY_CIRCUIT_ORDER: &[&str] = &["or", "nor", "and", "nand", "xor", "xnor", "not"];
 
 static COMPONENT_BUILTIN_ORDER: &[&str] = &[
     "button",
+    "switch",
+    "const_high",
+    "const_low",
+    "clock",
     "or",
     "nor",
     "and",
@@ -296,37 +456,29 @@ static COMPONENT_BUILTIN_ORDER: &[&str] = &[
     "xnor",
     "not",
     "transistor",
+    "tristate",
     "pullup",
+    "pulldown",
+    "sr_latch",
+    "d_latch",
+    "d_flip_flop",
+    "jk_flip_flop",
+    "t_flip_flop",
+    "mux",
+    "demux",
+    "decoder",
+    "priority_encoder",
     "freq_meter",
+    "led",
+    "rgb_led",
+    "seven_segment",
+    "hex_display",
+    "bus_splitter",
 ];
 
 impl App {
     pub fn create(cc: &CreationContext) -> Self {
-        let previews = [
-            Box::new(circuits::button::Preview {}) as Box<dyn CircuitPreviewImpl>,
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::or::TEMPLATE,
-            }),
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::nor::TEMPLATE,
-            }),
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::xor::TEMPLATE,
-            }),
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::xnor::TEMPLATE,
-            }),
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::and::TEMPLATE,
-            }),
-            Box::new(circuits::gates::gate::Preview {
-                template: circuits::gates::nand::TEMPLATE,
-            }),
-            Box::new(circuits::gates::not::Preview {}),
-            Box::new(circuits::pullup::Preview {}),
-            Box::new(circuits::transistor::Preview {}),
-            Box::new(circuits::freq_meter::Preview {}),
-        ];
+        let previews = circuits::builtin_previews();
         let preview_data = cc
             .storage
             .and_then(|s| s.get_string("previews"))
@@ -342,6 +494,14 @@ impl App {
             (p.imp.type_name(), Arc::new(p))
         }));
 
+        if let Some(limit) = cc
+            .storage
+            .and_then(|s| s.get_string("update_limit"))
+            .and_then(|s| s.parse().ok())
+        {
+            State::set_update_limit(limit);
+        }
+
         let ctx = BasicLoadingContext {
             previews: &previews,
         };
@@ -407,6 +567,25 @@ impl App {
             circuit_previews: previews,
             paste: None,
             props_ui: Default::default(),
+            step_ticks: 10,
+            last_step: None,
+            clock_scale: 1.0,
+            show_analyzer: false,
+            analyzer_zoom: 1.0,
+            #[cfg(not(feature = "wasm"))]
+            vcd_path: "trace.vcd".into(),
+            vcd_status: None,
+            show_states: false,
+            compare_state: None,
+            compare_differences_only: false,
+            show_conflicts: false,
+            show_breakpoints: false,
+            show_profiler: false,
+            profiler_sort: ProfilerColumn::Count,
+            profiler_descending: true,
+            last_stats: Default::default(),
+            rate_sample: (Instant::now(), 0),
+            updates_per_second: 0.0,
         }
     }
 
@@ -598,7 +777,7 @@ impl App {
     ) {
         if let SelectedItem::Circuit(pre) = selected_item {
             pre.props.write(id, f);
-            pre.prop_changed();
+            pre.prop_changed(id);
         } else {
             let selected_circuits: Vec<_> = self
                 .board
@@ -633,6 +812,884 @@ impl App {
         }
     }
 
+    fn analyzer_ui(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::bottom("analyzer")
+            .resizable(true)
+            .default_height(150.0)
+            .show(ctx, |ui| {
+                let state = self.board.state.clone();
+                let now = state.sim_time();
+
+                ui.horizontal(|ui| {
+                    let mut analyzer = state.analyzer.lock();
+                    let recording = analyzer.recording;
+                    if ui.button(if recording { "Stop" } else { "Record" }).clicked() {
+                        analyzer.recording = !recording;
+                    }
+                    if ui.button("Clear").clicked() {
+                        analyzer.clear();
+                    }
+                    drop(analyzer);
+
+                    if ui.button("Add selected").clicked() {
+                        self.add_selected_probes();
+                    }
+                    ui.add(
+                        Slider::new(&mut self.analyzer_zoom, 0.01..=1e9)
+                            .logarithmic(true)
+                            .text("ns per pixel"),
+                    );
+
+                    if ui.button("Copy VCD").clicked() {
+                        let mut vcd = String::new();
+                        state.analyzer.lock().write_vcd(&mut vcd, now).unwrap();
+                        ui.output_mut(|output| output.copied_text = vcd);
+                    }
+
+                    #[cfg(not(feature = "wasm"))]
+                    {
+                        ui.text_edit_singleline(&mut self.vcd_path);
+                        if ui.button("Save VCD").clicked() {
+                            let mut vcd = String::new();
+                            state.analyzer.lock().write_vcd(&mut vcd, now).unwrap();
+                            self.vcd_status = Some(match std::fs::write(&self.vcd_path, vcd) {
+                                Ok(()) => format!("Saved {}", self.vcd_path),
+                                Err(e) => format!("Can't save {}: {e}", self.vcd_path),
+                            });
+                        }
+                    }
+
+                    if let Some(status) = &self.vcd_status {
+                        ui.label(status);
+                    }
+                });
+                ui.separator();
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    Self::timing_diagram_ui(ui, &state, now, self.analyzer_zoom);
+                });
+            });
+    }
+
+    const STATE_DIFFERENCE_COLOR: Color32 = Color32::from_rgb(255, 200, 0);
+
+    fn states_ui(&mut self, ctx: &Context) {
+        egui::SidePanel::right("states")
+            .resizable(true)
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                let board = self.board.board.clone();
+                let states = board.read().states.clone();
+                let ids: Vec<_> = {
+                    let vec = states.states().read();
+                    vec.inner()
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, s)| s.as_ref().map(|_| i))
+                        .collect()
+                };
+
+                ui.horizontal(|ui| {
+                    if ui.button("New").clicked() {
+                        let (id, state) = states.create_state(board.clone());
+                        state.init();
+                        self.board.set_state(id);
+                    }
+                    if ui.button("Clone").clicked() {
+                        if let Some((id, _)) = states.clone_state(self.board.state_id) {
+                            self.board.set_state(id);
+                        }
+                    }
+                    let can_delete = ids.len() > 1;
+                    if ui.add_enabled(can_delete, egui::Button::new("Delete")).clicked() {
+                        let removed = self.board.state_id;
+                        let next = ids.iter().copied().find(|id| *id != removed);
+                        if let Some(next) = next {
+                            self.board.set_state(next);
+                            states.remove_state(removed);
+                            if self.compare_state == Some(removed) {
+                                self.compare_state = None;
+                            }
+                        }
+                    }
+                });
+
+                let mut name = self.board.state.name();
+                ui.horizontal(|ui| {
+                    ui.monospace("Name:");
+                    if ui.text_edit_singleline(&mut name).changed() {
+                        self.board.state.set_name(name);
+                    }
+                });
+                ui.separator();
+
+                for &id in ids.iter() {
+                    let name = states.state_name(id).unwrap_or_default();
+                    let active = id == self.board.state_id;
+                    if ui.selectable_label(active, name).clicked() && !active {
+                        self.board.set_state(id);
+                    }
+                }
+                ui.separator();
+
+                if self.compare_state.is_some_and(|id| !ids.contains(&id)) {
+                    self.compare_state = None;
+                }
+                let compare_name = self
+                    .compare_state
+                    .and_then(|id| states.state_name(id))
+                    .unwrap_or_else(|| "None".into());
+                ui.horizontal(|ui| {
+                    ui.monospace("Compare with:");
+                    ComboBox::from_id_source("compare_state")
+                        .selected_text(compare_name)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.compare_state, None, "None");
+                            for &id in ids.iter().filter(|id| **id != self.board.state_id) {
+                                let name = states.state_name(id).unwrap_or_default();
+                                ui.selectable_value(&mut self.compare_state, Some(id), name);
+                            }
+                        });
+                });
+
+                let other_id = unwrap_option_or_return!(self.compare_state);
+                let other = unwrap_option_or_return!(states.get(other_id));
+                ui.checkbox(&mut self.compare_differences_only, "Differences only");
+
+                let mut wires: Vec<_> = board.read().wires.iter().map(|w| w.id).collect();
+                wires.sort_unstable();
+
+                let rows: Vec<_> = wires
+                    .into_iter()
+                    .map(|wire| {
+                        let a = self.board.state.read_wire(wire);
+                        (wire, [a, other.read_wire(wire)])
+                    })
+                    .filter(|(_, [a, b])| !self.compare_differences_only || a != b)
+                    .collect();
+
+                // Both states get their own column, rows line up since they list the same wires
+                ScrollArea::vertical().show(ui, |ui| {
+                    ui.columns(2, |columns| {
+                        let ids = [self.board.state_id, other_id];
+                        for (side, ui) in columns.iter_mut().enumerate() {
+                            ui.monospace(states.state_name(ids[side]).unwrap_or_default());
+                            egui::Grid::new(("state_compare", side))
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for (wire, values) in rows.iter() {
+                                        let color = match values[0] == values[1] {
+                                            true => ui.style().visuals.text_color(),
+                                            false => Self::STATE_DIFFERENCE_COLOR,
+                                        };
+                                        let state = values[side];
+                                        let width = state.width().unwrap_or(1);
+                                        let text = crate::analyzer::value_text(state, width);
+                                        ui.monospace(wire.to_string());
+                                        ui.colored_label(color, RichText::new(text).monospace());
+                                        ui.end_row();
+                                    }
+                                });
+                        }
+                    });
+                });
+            });
+    }
+
+    fn breakpoint_target_text(board: &CircuitBoard, target: &BreakpointTarget) -> String {
+        match target {
+            BreakpointTarget::Wire(wire) => format!("wire {wire}"),
+            BreakpointTarget::Pin { circuit, pin } => {
+                let name = board
+                    .circuits
+                    .get(*circuit)
+                    .and_then(|c| {
+                        c.props
+                            .read("name", |s: &ArcString| s.get_arc())
+                            .filter(|n| !n.is_empty())
+                            .map(|n| n.to_string())
+                            .or_else(|| Some(format!("{}_{circuit}", c.ty.deref())))
+                    })
+                    .unwrap_or_else(|| format!("circuit {circuit}"));
+                format!("{name}.{}", pin.deref())
+            }
+        }
+    }
+
+    fn breakpoints_ui(&mut self, ctx: &Context) {
+        let mut open = self.show_breakpoints;
+        egui::Window::new("Breakpoints")
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                let board = self.board.board.clone();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add selected").clicked() {
+                        self.add_selected_breakpoints();
+                    }
+                    if let Some(hit) = self.board.state.breakpoint_hit() {
+                        let target = Self::breakpoint_target_text(
+                            &board.read(),
+                            &hit.breakpoint.target,
+                        );
+                        ui.colored_label(
+                            ActiveCircuitBoard::OSCILLATION_HIGHLIGHT,
+                            format!("Hit {target}"),
+                        );
+                        if ui.button("Continue").clicked() {
+                            self.board.state.set_paused(false);
+                        }
+                    }
+                });
+                ui.separator();
+
+                let board = board.read();
+                let mut breakpoints = board.breakpoints.write();
+                let mut remove = None;
+                for (i, breakpoint) in breakpoints.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut breakpoint.enabled, "");
+                        match &mut breakpoint.target {
+                            BreakpointTarget::Wire(wire) => {
+                                ui.monospace(format!("wire {wire}"));
+                            }
+                            BreakpointTarget::Pin { circuit, pin } => {
+                                Self::breakpoint_pin_ui(ui, &board, i, *circuit, pin);
+                            }
+                        }
+
+                        let condition = &mut breakpoint.condition;
+                        ComboBox::from_id_source(("breakpoint_condition", i))
+                            .selected_text(Self::breakpoint_condition_text(*condition))
+                            .show_ui(ui, |ui| {
+                                for option in [
+                                    BreakpointCondition::Changes,
+                                    BreakpointCondition::Becomes(WireState::True),
+                                    BreakpointCondition::Becomes(WireState::False),
+                                    BreakpointCondition::Becomes(WireState::None),
+                                    BreakpointCondition::Becomes(WireState::Error),
+                                ] {
+                                    let text = Self::breakpoint_condition_text(option);
+                                    ui.selectable_value(condition, option, text);
+                                }
+                            });
+
+                        if ui.button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(index) = remove {
+                    breakpoints.remove(index);
+                }
+            });
+        self.show_breakpoints = open;
+    }
+
+    fn breakpoint_pin_ui(
+        ui: &mut Ui,
+        board: &CircuitBoard,
+        index: usize,
+        circuit: usize,
+        pin: &mut DynStaticStr,
+    ) {
+        let target = BreakpointTarget::Pin {
+            circuit,
+            pin: pin.clone(),
+        };
+        let pins: Vec<_> = board
+            .circuits
+            .get(circuit)
+            .map(|c| c.info.read().pins.iter().map(|p| p.name.clone()).collect())
+            .unwrap_or_default();
+        ComboBox::from_id_source(("breakpoint_pin", index))
+            .selected_text(Self::breakpoint_target_text(board, &target))
+            .show_ui(ui, |ui| {
+                for name in pins {
+                    let selected = *pin == *name;
+                    if ui.selectable_label(selected, name.deref()).clicked() {
+                        *pin = name;
+                    }
+                }
+            });
+    }
+
+    fn breakpoint_condition_text(condition: BreakpointCondition) -> String {
+        match condition {
+            BreakpointCondition::Changes => "changes".into(),
+            BreakpointCondition::Becomes(state) => format!("becomes {state}"),
+        }
+    }
+
+    /// Adds breakpoints on selected wires and first pins of selected circuits
+    fn add_selected_breakpoints(&self) {
+        let board = self.board.board.read();
+        let selection = self.board.selection.borrow();
+
+        let mut targets = vec![];
+        for obj in selection.selection.iter() {
+            match obj {
+                SelectedWorldObject::WirePart { pos, dir } => {
+                    if let Some(wire) = self.board.wire_at(*pos, (*dir).into()) {
+                        targets.push(BreakpointTarget::Wire(wire));
+                    }
+                }
+                SelectedWorldObject::Circuit { id } => {
+                    let circuit = unwrap_option_or_continue!(board.circuits.get(*id));
+                    let info = circuit.info.read();
+                    let pin = unwrap_option_or_continue!(info.pins.first());
+                    targets.push(BreakpointTarget::Pin {
+                        circuit: *id,
+                        pin: pin.name.clone(),
+                    });
+                }
+            }
+        }
+
+        let mut breakpoints = board.breakpoints.write();
+        for target in targets {
+            if !breakpoints.iter().any(|b| b.target == target) {
+                breakpoints.push(Breakpoint::new(target));
+            }
+        }
+    }
+
+    /// Lists drivers of every wire in error state, clicking one pans to it
+    fn conflicts_ui(&mut self, ctx: &Context) {
+        let view_size = Vec2f::from(ctx.available_rect().size());
+        let mut pan_to = None;
+        let mut open = self.show_conflicts;
+
+        egui::Window::new("Conflicts")
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                let board = self.board.board.read();
+                let state = &self.board.state;
+                let mut wires: Vec<_> = board
+                    .wires
+                    .iter()
+                    .filter(|w| state.read_wire(w.id).has_error())
+                    .collect();
+                wires.sort_unstable_by_key(|w| w.id);
+
+                if wires.is_empty() {
+                    ui.label("No wires in error state");
+                    return;
+                }
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    for wire in wires {
+                        CollapsingHeader::new(format!("Wire {}", wire.id))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                let drivers = state.wire_drivers(wire);
+                                if drivers.is_empty() {
+                                    ui.label("No drivers, error comes from width mismatch");
+                                }
+                                for driver in drivers {
+                                    let circuit = board.circuits.get(driver.pin.circuit_id);
+                                    let circuit = unwrap_option_or_continue!(circuit);
+                                    let ty = self
+                                        .circuit_previews
+                                        .get(&circuit.ty)
+                                        .map(|p| p.imp.display_name())
+                                        .unwrap_or_else(|| circuit.ty.clone());
+                                    let name = circuit
+                                        .props
+                                        .read("name", |s: &ArcString| s.get_arc())
+                                        .filter(|n| !n.is_empty())
+                                        .map(|n| format!(" {n}"))
+                                        .unwrap_or_default();
+                                    let pin = circuit
+                                        .info
+                                        .read()
+                                        .pins
+                                        .get(driver.pin.id)
+                                        .map(|p| p.name.clone())
+                                        .unwrap_or_else(|| "?".into());
+                                    let width = driver.state.width().unwrap_or(1);
+                                    let value =
+                                        crate::analyzer::value_text(driver.state, width);
+                                    let text = format!(
+                                        "{}{name}.{} at {}, {}: {value}",
+                                        ty.deref(),
+                                        pin.deref(),
+                                        driver.pos.x(),
+                                        driver.pos.y(),
+                                    );
+                                    let text =
+                                        RichText::new(text).monospace().color(driver.state.color());
+                                    if ui.selectable_label(false, text).clicked() {
+                                        pan_to = Some(driver.pos);
+                                    }
+                                }
+                            });
+                    }
+                });
+            });
+
+        self.show_conflicts = open;
+        if let Some(pos) = pan_to {
+            let world = pos.convert(|v| v as f32) + 0.5;
+            self.pan_zoom.center_on(world, view_size);
+        }
+    }
+
+    fn profiler_ui(&mut self, ctx: &Context) {
+        let view_size = Vec2f::from(ctx.available_rect().size());
+        let mut pan_to = None;
+        let mut open = self.show_profiler;
+
+        egui::Window::new("Profiler")
+            .open(&mut open)
+            .default_width(350.0)
+            .show(ctx, |ui| {
+                let state = &self.board.state;
+                if ui.button("Reset counters").clicked() {
+                    state.clear_profile();
+                }
+
+                let profile = state.profile();
+                let board = self.board.board.read();
+                let circuits = profile.circuit_updates.iter().filter_map(|(id, count)| {
+                    let circuit = board.circuits.get(*id)?;
+                    let ty = self
+                        .circuit_previews
+                        .get(&circuit.ty)
+                        .map(|p| p.imp.display_name())
+                        .unwrap_or_else(|| circuit.ty.clone());
+                    let name = match circuit.props.read("name", |s: &ArcString| s.get_arc()) {
+                        Some(name) if !name.is_empty() => format!("{} {name}", ty.deref()),
+                        _ => ty.deref().into(),
+                    };
+                    Some(ProfilerRow {
+                        kind: "Circuit",
+                        id: *id,
+                        name,
+                        count: *count,
+                        pos: circuit.pos,
+                    })
+                });
+                let wires = profile.wire_changes.iter().filter_map(|(id, count)| {
+                    let wire = board.wires.get(*id)?;
+                    let pos = wire.points.keys().min_by_key(|p| (p.y(), p.x()))?;
+                    Some(ProfilerRow {
+                        kind: "Wire",
+                        id: *id,
+                        name: String::new(),
+                        count: *count,
+                        pos: *pos,
+                    })
+                });
+                let mut rows: Vec<_> = circuits.chain(wires).collect();
+                drop(board);
+
+                rows.sort_unstable_by(|a, b| {
+                    let order = match self.profiler_sort {
+                        ProfilerColumn::Kind => a.kind.cmp(b.kind),
+                        ProfilerColumn::Id => a.id.cmp(&b.id),
+                        ProfilerColumn::Name => a.name.cmp(&b.name),
+                        ProfilerColumn::Count => a.count.cmp(&b.count),
+                    };
+                    let order = order.then_with(|| (a.kind, a.id).cmp(&(b.kind, b.id)));
+                    match self.profiler_descending {
+                        true => order.reverse(),
+                        false => order,
+                    }
+                });
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("profiler").striped(true).show(ui, |ui| {
+                        for column in ProfilerColumn::ALL {
+                            let mut text = column.name().to_owned();
+                            if column == self.profiler_sort {
+                                text.push_str(if self.profiler_descending { " v" } else { " ^" });
+                            }
+                            if ui.selectable_label(column == self.profiler_sort, text).clicked() {
+                                if column == self.profiler_sort {
+                                    self.profiler_descending = !self.profiler_descending;
+                                } else {
+                                    self.profiler_sort = column;
+                                    // Busiest first is what's usually wanted
+                                    self.profiler_descending = column == ProfilerColumn::Count;
+                                }
+                            }
+                        }
+                        ui.end_row();
+
+                        for row in rows {
+                            let clicked = [
+                                ui.selectable_label(false, row.kind),
+                                ui.selectable_label(false, row.id.to_string()),
+                                ui.selectable_label(false, row.name),
+                                ui.selectable_label(false, row.count.to_string()),
+                            ]
+                            .iter()
+                            .any(|r| r.clicked());
+                            if clicked {
+                                pan_to = Some(row.pos);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        self.show_profiler = open;
+        if let Some(pos) = pan_to {
+            let world = pos.convert(|v| v as f32) + 0.5;
+            self.pan_zoom.center_on(world, view_size);
+        }
+    }
+
+    fn timing_diagram_ui(ui: &mut Ui, state: &State, now: SimTime, ns_per_pixel: f32) {
+        const ROW_HEIGHT: f32 = 20.0;
+        const NAME_WIDTH: f32 = 150.0;
+
+        let mut analyzer = state.analyzer.lock();
+        let start = analyzer.start_time().unwrap_or(now);
+        let mut remove = None;
+
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.spacing_mut().item_spacing.y = 0.0;
+                for (i, probe) in analyzer.probes.iter().enumerate() {
+                    ui.allocate_ui_with_layout(
+                        vec2(NAME_WIDTH, ROW_HEIGHT),
+                        Layout::left_to_right(Align::Center),
+                        |ui| {
+                            ui.set_min_size(vec2(NAME_WIDTH, ROW_HEIGHT));
+                            if ui.small_button("x").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.monospace(&probe.name);
+                        },
+                    );
+                }
+            });
+
+            ScrollArea::horizontal()
+                .stick_to_right(true)
+                .show(ui, |ui| {
+                    let width = ((now - start) as f32 / ns_per_pixel).max(ui.available_width());
+                    let height = ROW_HEIGHT * analyzer.probes.len() as f32;
+                    let (rect, _) = ui.allocate_exact_size(vec2(width, height), Sense::hover());
+                    let visible = ui.clip_rect();
+                    let paint = ui.painter();
+                    let font = FontId::monospace(10.0);
+                    let x = |time: SimTime| rect.left() + (time - start) as f32 / ns_per_pixel;
+
+                    for (i, probe) in analyzer.probes.iter().enumerate() {
+                        let row = Rect::from_min_size(
+                            pos2(rect.left(), rect.top() + i as f32 * ROW_HEIGHT),
+                            vec2(width, ROW_HEIGHT),
+                        )
+                        .shrink2(vec2(0.0, 4.0));
+
+                        let mut changes = probe.changes.iter().peekable();
+                        while let Some(&(time, wire_state)) = changes.next() {
+                            let end = changes.peek().map_or(now, |(t, _)| *t);
+                            let (x0, x1) = (x(time), x(end.max(time)));
+                            if x1 < visible.left() || x0 > visible.right() {
+                                continue;
+                            }
+                            let color = wire_state.color();
+                            let stroke = Stroke::new(1.5, color);
+
+                            if probe.width == 1 {
+                                let y = match wire_state {
+                                    WireState::True => row.top(),
+                                    WireState::False => row.bottom(),
+                                    _ => row.center().y,
+                                };
+                                let edge = [pos2(x0, row.top()), pos2(x0, row.bottom())];
+                                paint.line_segment(edge, stroke);
+                                paint.line_segment([pos2(x0, y), pos2(x1, y)], stroke);
+                            } else {
+                                let segment = Rect::from_x_y_ranges(x0..=x1, row.y_range());
+                                paint.rect_stroke(segment, Rounding::same(2.0), stroke);
+
+                                let text = crate::analyzer::value_text(wire_state, probe.width);
+                                // Only draw values that fit
+                                if text.len() as f32 * 7.0 < x1 - x0 {
+                                    paint.text(
+                                        segment.center(),
+                                        Align2::CENTER_CENTER,
+                                        text,
+                                        font.clone(),
+                                        color,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                });
+        });
+
+        if let Some(index) = remove {
+            analyzer.remove_probe(index);
+        }
+    }
+
+    /// Adds selected wires and wires connected to pins of selected circuits to the logic analyzer
+    fn add_selected_probes(&self) {
+        let board = self.board.board.read();
+        let selection = self.board.selection.borrow();
+
+        let mut probes = vec![];
+        for obj in selection.selection.iter() {
+            match obj {
+                SelectedWorldObject::WirePart { pos, dir } => {
+                    if let Some(wire) = self.board.wire_at(*pos, (*dir).into()) {
+                        probes.push((format!("wire_{wire}"), wire));
+                    }
+                }
+                SelectedWorldObject::Circuit { id } => {
+                    let circuit = unwrap_option_or_continue!(board.circuits.get(*id));
+                    let name = circuit
+                        .props
+                        .read("name", |s: &ArcString| s.get_arc())
+                        .filter(|n| !n.is_empty())
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| format!("{}_{id}", circuit.ty.deref()));
+                    for pin in circuit.info.read().pins.iter() {
+                        if let Some(wire) = pin.pin.read().connected_wire() {
+                            probes.push((format!("{name}.{}", pin.name.deref()), wire));
+                        }
+                    }
+                }
+            }
+        }
+        // Selection isn't ordered
+        probes.sort();
+
+        let state = &self.board.state;
+        let time = state.sim_time();
+        for (name, wire) in probes {
+            let width = board
+                .wires
+                .get(wire)
+                .and_then(|w| {
+                    w.points
+                        .values()
+                        .find_map(|p| p.pin.as_ref().map(|p| p.read().width()))
+                })
+                .unwrap_or(1);
+            let wire_state = state.read_wire(wire);
+            state
+                .analyzer
+                .lock()
+                .add_probe(wire, name, width, time, wire_state);
+        }
+    }
+
+    fn step_simulation(&mut self, step: SimulationStep) {
+        let state = &self.board.state;
+        state.set_paused(true);
+        let (task, _) = state.step(step);
+        if task.is_some() {
+            self.last_step = task;
+        }
+    }
+
+    /// Updates throughput measurement, returns time spent simulating since previous frame
+    fn measure_stats(&mut self) -> std::time::Duration {
+        const RATE_INTERVAL: f32 = 0.5;
+
+        let stats = self.board.state.stats();
+        // Stats of a different state after switching
+        let busy = stats.busy.saturating_sub(self.last_stats.busy);
+        self.last_stats = stats;
+
+        let now = Instant::now();
+        let elapsed = (now - self.rate_sample.0).as_secs_f32();
+        if elapsed >= RATE_INTERVAL {
+            let tasks = stats.tasks.saturating_sub(self.rate_sample.1);
+            self.updates_per_second = tasks as f32 / elapsed;
+            self.rate_sample = (now, stats.tasks);
+        }
+        busy
+    }
+
+    fn simulation_controls_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let paused = self.board.state.is_paused();
+            if ui.button(if paused { "Run" } else { "Pause" }).clicked() {
+                self.board.state.set_paused(!paused);
+            }
+            if ui.button("Step").clicked() {
+                self.step_simulation(SimulationStep::Task);
+            }
+            if ui.button("Settle").clicked() {
+                self.step_simulation(SimulationStep::Settle);
+            }
+            if ui.button("Ticks").clicked() {
+                self.step_simulation(SimulationStep::Ticks(self.step_ticks));
+            }
+            ui.add(DragValue::new(&mut self.step_ticks).clamp_range(1..=1_000_000));
+        });
+        ui.horizontal(|ui| {
+            let state = &self.board.state;
+            let mut mode = state.clock_mode();
+            if let ClockMode::Scaled(scale) = mode {
+                self.clock_scale = scale;
+            }
+
+            ui.monospace("Clock:");
+            ComboBox::from_id_source("clock_mode")
+                .selected_text(mode.name())
+                .show_ui(ui, |ui| {
+                    for option in [
+                        ClockMode::AsFastAsPossible,
+                        ClockMode::RealTime,
+                        ClockMode::Scaled(self.clock_scale),
+                    ] {
+                        let selected =
+                            std::mem::discriminant(&mode) == std::mem::discriminant(&option);
+                        if ui.selectable_label(selected, option.name()).clicked() {
+                            mode = option;
+                        }
+                    }
+                });
+            if let ClockMode::Scaled(scale) = &mut mode {
+                ui.add(
+                    DragValue::new(scale)
+                        .speed(0.01)
+                        .clamp_range(0.000_001..=1_000_000.0)
+                        .suffix("x"),
+                );
+            }
+
+            if mode != state.clock_mode() {
+                state.set_clock_mode(mode);
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut limit = State::update_limit();
+            let mut limited = limit > 0;
+            ui.checkbox(&mut limited, "Limit");
+            if limited {
+                limit = limit.max(1);
+                ui.add(
+                    DragValue::new(&mut limit)
+                        .clamp_range(1..=100_000_000)
+                        .suffix(" updates/s"),
+                );
+            } else {
+                limit = 0;
+            }
+            if limit != State::update_limit() {
+                State::set_update_limit(limit);
+            }
+        });
+        ui.horizontal(|ui| {
+            let state = &self.board.state;
+            let ordered = self.board.board.read().is_ordered_queue();
+            ui.add_enabled_ui(!ordered, |ui| {
+                let mut seed = state.seed();
+                ui.monospace("Seed:");
+                if ui.add(DragValue::new(&mut seed)).changed() {
+                    state.set_seed(seed);
+                }
+                if ui
+                    .button("Re-run")
+                    .on_hover_text("Reset and run again with this seed")
+                    .clicked()
+                {
+                    state.rerun(seed);
+                }
+                if ui.button("New seed").clicked() {
+                    state.rerun(SeededState::random_seed());
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.show_profiler, "Profiler");
+            ui.checkbox(&mut self.board.heat_map, "Heat map");
+
+            let mut compiled = self.board.board.read().is_compiled();
+            let response = ui
+                .checkbox(&mut compiled, "Compiled")
+                .on_hover_text("Evaluate combinational gates in levelized passes");
+            if response.changed() {
+                let sim_lock = self.board.board.read().sim_lock.clone();
+                let sim_lock = sim_lock.write();
+                self.board.board.write().set_compiled(compiled, false);
+                drop(sim_lock);
+            }
+            if compiled {
+                let regions = self.board.board.read().compiled_regions();
+                let circuits = regions.map(|r| r.circuits()).unwrap_or_default();
+                ui.monospace(format!("{circuits} circuits"));
+            }
+        });
+        ui.horizontal(|ui| {
+            let state = &self.board.state;
+            let times = state.history_times();
+            ui.monospace("History:");
+
+            if !times.is_empty() {
+                let now = state.sim_time();
+                let current = times.iter().rposition(|t| *t <= now).unwrap_or(0);
+                let mut index = current;
+                ui.add(
+                    Slider::new(&mut index, 0..=times.len() - 1)
+                        .show_value(false)
+                        .text(format!("{} ns", times[current])),
+                );
+                if index != current {
+                    state.rewind(index);
+                }
+            }
+
+            let mut interval = state.history_interval();
+            ui.monospace("every");
+            let interval_drag = DragValue::new(&mut interval)
+                .clamp_range(1..=SimTime::MAX)
+                .suffix(" ns");
+            if ui.add(interval_drag).changed() {
+                state.set_history_interval(interval);
+            }
+        });
+    }
+
+    fn diagnostics_ui(&mut self, ui: &mut Ui) {
+        let state = &self.board.state;
+        ui.horizontal(|ui| {
+            let mut budget = state.oscillation_budget();
+            ui.monospace("Oscillation budget:");
+            if ui
+                .add(DragValue::new(&mut budget).clamp_range(1..=1_000_000))
+                .changed()
+            {
+                state.set_oscillation_budget(budget);
+            }
+        });
+
+        let diagnostics = state.diagnostics();
+        if diagnostics.is_empty() {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.monospace("Diagnostics:");
+            if ui.button("Clear").clicked() {
+                state.clear_oscillations();
+            }
+        });
+        for diagnostic in diagnostics {
+            let color = match diagnostic {
+                Diagnostic::WidthMismatch { .. } => WireState::Error.color(),
+                Diagnostic::OscillatingWire { .. } | Diagnostic::OscillatingCircuit { .. } => {
+                    ActiveCircuitBoard::OSCILLATION_HIGHLIGHT
+                }
+            };
+            ui.colored_label(color, RichText::new(diagnostic.to_string()).monospace());
+        }
+    }
+
     fn selected_item(&self) -> SelectedItem {
         match self.selected_id.as_deref() {
             None => SelectedItem::None,
@@ -0,0 +1,332 @@
+//! Headless simulation runner.
+//!
+//! Loads a saved board, drives named circuit outputs, simulates until circuits settle
+//! or for given simulation time and prints named pin and wire states
+
+use std::{
+    ops::Deref,
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rls::{
+    board::CircuitBoard,
+    circuits::{self, Circuit, CircuitStateContext},
+    io::CircuitBoardData,
+    state::{SimTime, SimulationStep, State, WireState},
+    ArcString, BasicLoadingContext,
+};
+
+const USAGE: &str = "\
+Usage: rls-cli <board.ron> [options]
+
+Options:
+    --state <id>            Simulate state with this id, first existing state by default
+    --set <name>[.<pin>]=<value>
+                            Drive output pin of a named circuit, first pin if not specified.
+                            Value is written most significant bit first: 0, 1, z or x for
+                            each bit, or 0x-prefixed hex
+    --print <name>[.<pin>]  Print pin states of a named circuit.
+                            Every pin of every named circuit is printed by default
+    --wire <id>             Print wire state
+    --time <ns>             Simulate this much time instead of waiting for simulation to settle
+    --compiled              Evaluate zero-delay gates in compiled regions instead of the queue
+    --seed <seed>           Seed of the unordered update queue, saved one by default
+    --timeout <ms>          Give up after this much real time, 5000 by default";
+
+/// Simulation time to advance per step, so timeout is checked regularly
+const STEP_TICKS: SimTime = 1_000_000;
+
+struct Args {
+    board: String,
+    state: Option<usize>,
+    set: Vec<(PinPath, String)>,
+    print: Vec<PinPath>,
+    wires: Vec<usize>,
+    time: Option<SimTime>,
+    timeout: Duration,
+    compiled: bool,
+    seed: Option<u64>,
+}
+
+struct PinPath {
+    circuit: String,
+    pin: Option<String>,
+}
+
+impl PinPath {
+    fn parse(s: &str) -> Self {
+        match s.split_once('.') {
+            Some((circuit, pin)) => Self {
+                circuit: circuit.into(),
+                pin: Some(pin.into()),
+            },
+            None => Self {
+                circuit: s.into(),
+                pin: None,
+            },
+        }
+    }
+}
+
+/// None if help was requested
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = std::env::args().skip(1);
+    let mut board = None;
+    let mut parsed = Args {
+        board: String::new(),
+        state: None,
+        set: vec![],
+        print: vec![],
+        wires: vec![],
+        time: None,
+        timeout: Duration::from_millis(5000),
+        compiled: false,
+        seed: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {name}"))
+        };
+        match arg.as_str() {
+            "--state" => {
+                let id = value("--state")?;
+                parsed.state = Some(id.parse().map_err(|_| format!("Invalid state id {id}"))?);
+            }
+            "--set" => {
+                let set = value("--set")?;
+                let (path, value) = set
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected <name>=<value>, got {set}"))?;
+                parsed.set.push((PinPath::parse(path), value.into()));
+            }
+            "--print" => parsed.print.push(PinPath::parse(&value("--print")?)),
+            "--wire" => {
+                let id = value("--wire")?;
+                parsed
+                    .wires
+                    .push(id.parse().map_err(|_| format!("Invalid wire id {id}"))?);
+            }
+            "--time" => {
+                let ns = value("--time")?;
+                parsed.time = Some(ns.parse().map_err(|_| format!("Invalid time {ns}"))?);
+            }
+            "--timeout" => {
+                let ms = value("--timeout")?;
+                let ms = ms.parse().map_err(|_| format!("Invalid timeout {ms}"))?;
+                parsed.timeout = Duration::from_millis(ms);
+            }
+            "--compiled" => parsed.compiled = true,
+            "--seed" => {
+                let seed = value("--seed")?;
+                parsed.seed = Some(seed.parse().map_err(|_| format!("Invalid seed {seed}"))?);
+            }
+            "-h" | "--help" => return Ok(None),
+            _ if board.is_none() && !arg.starts_with("--") => board = Some(arg),
+            _ => return Err(format!("Unexpected argument {arg}")),
+        }
+    }
+
+    parsed.board = board.ok_or_else(|| USAGE.to_string())?;
+    Ok(Some(parsed))
+}
+
+fn parse_state(value: &str, width: u32) -> Result<WireState, String> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        let value = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid hex {value}"))?;
+        if value.checked_shr(width).unwrap_or(0) != 0 {
+            return Err(format!("Value 0x{hex} doesn't fit into pin width {width}"));
+        }
+        let bits = (0..width).map(|i| WireState::from((value >> i) & 1 != 0));
+        return Ok(WireState::from_bits(width, bits));
+    }
+
+    let bits: Vec<_> = value
+        .chars()
+        .rev()
+        .map(|c| match c {
+            '0' => Ok(WireState::False),
+            '1' => Ok(WireState::True),
+            'z' | 'Z' => Ok(WireState::None),
+            'x' | 'X' => Ok(WireState::Error),
+            _ => Err(format!("Invalid value {value}")),
+        })
+        .collect::<Result<_, _>>()?;
+    if bits.len() != width as usize {
+        return Err(format!("Value {value} doesn't match pin width {width}"));
+    }
+    Ok(WireState::from_bits(width, bits))
+}
+
+fn circuit_name(circuit: &Circuit) -> Option<Arc<str>> {
+    circuit
+        .props
+        .read("name", |s: &ArcString| s.get_arc())
+        .filter(|n| !n.is_empty())
+}
+
+fn find_circuit<'a>(board: &'a CircuitBoard, name: &str) -> Result<&'a Circuit, String> {
+    board
+        .circuits
+        .iter()
+        .find(|c| circuit_name(c).is_some_and(|n| n.deref() == name))
+        .ok_or_else(|| format!("No circuit named {name}"))
+}
+
+fn drive(board: &CircuitBoard, state: &State, path: &PinPath, value: &str) -> Result<(), String> {
+    let circuit = find_circuit(board, &path.circuit)?;
+    let info = circuit.info.read();
+    let pin = match &path.pin {
+        None => info.pins.first(),
+        Some(name) => info.pins.iter().find(|p| p.name.deref() == name),
+    }
+    .ok_or_else(|| format!("No such pin on {}", path.circuit))?;
+
+    let value = parse_state(value, pin.width)?;
+    pin.set_state(&CircuitStateContext::new(state, circuit), value);
+    Ok(())
+}
+
+fn print_circuit(state: &State, circuit: &Circuit, pin: Option<&str>) {
+    let name = circuit_name(circuit).unwrap_or_else(|| circuit.id.to_string().into());
+    let ctx = CircuitStateContext::new(state, circuit);
+    let info = circuit.info.read();
+    for pin_info in info.pins.iter() {
+        if pin.is_some_and(|p| p != pin_info.name.deref()) {
+            continue;
+        }
+        let value = pin_info
+            .get_wire_state(&ctx)
+            .unwrap_or_else(|| pin_info.get_state(&ctx));
+        println!("{name}.{} = {value}", pin_info.name.deref());
+    }
+}
+
+fn run(args: Args) -> Result<bool, String> {
+    let data = std::fs::read_to_string(&args.board)
+        .map_err(|e| format!("Can't read {}: {e}", args.board))?;
+    let data: CircuitBoardData =
+        ron::from_str(&data).map_err(|e| format!("Can't parse {}: {e}", args.board))?;
+
+    let previews = circuits::builtin_preview_map();
+    let board = CircuitBoard::load(
+        &data,
+        &BasicLoadingContext {
+            previews: &previews,
+        },
+    );
+    if args.compiled {
+        board.write().set_compiled(true, false);
+    }
+
+    // Simulation runs on this thread, so it's paused before anything gets scheduled
+    let state = {
+        let board_ref = board.read();
+        let existing = match args.state {
+            Some(id) => Some(
+                board_ref
+                    .states
+                    .get(id)
+                    .ok_or_else(|| format!("No state with id {id}"))?,
+            ),
+            None => board_ref.states.states().read().iter().next().cloned(),
+        };
+        match existing {
+            Some(state) => {
+                state.set_paused(true);
+                state
+            }
+            None => {
+                let (_, state) = board_ref.states.create_state(board.clone());
+                state.set_paused(true);
+                for circuit in board_ref.circuits.iter() {
+                    board_ref.states.init_circuit(circuit);
+                }
+                state.update_everything();
+                state
+            }
+        }
+    };
+    if let Some(seed) = args.seed {
+        state.set_seed(seed);
+    }
+
+    // Initial updates would override driven pins otherwise
+    state.step(SimulationStep::Settle);
+    {
+        let board = board.read();
+        for (path, value) in args.set.iter() {
+            drive(&board, &state, path, value)?;
+        }
+    }
+
+    let start = Instant::now();
+    let until = args.time.map(|time| state.sim_time() + time);
+    let finished = |state: &State| match until {
+        Some(until) => state.sim_time() >= until,
+        None => state.is_settled(),
+    };
+    while !finished(&state) && Instant::now() - start < args.timeout {
+        let ticks = match until {
+            Some(until) => (until - state.sim_time()).min(STEP_TICKS),
+            None => STEP_TICKS,
+        };
+        state.step(SimulationStep::Ticks(ticks));
+
+        // Won't settle anyway
+        if until.is_none() && !state.oscillating_wires().is_empty() {
+            break;
+        }
+    }
+    let finished = finished(&state);
+    for diagnostic in state.diagnostics() {
+        eprintln!("{diagnostic}");
+    }
+
+    let board = board.read();
+    if args.print.is_empty() && args.wires.is_empty() {
+        for circuit in board.circuits.iter() {
+            if circuit_name(circuit).is_some() {
+                print_circuit(&state, circuit, None);
+            }
+        }
+    }
+    for path in args.print.iter() {
+        let circuit = find_circuit(&board, &path.circuit)?;
+        print_circuit(&state, circuit, path.pin.as_deref());
+    }
+    for wire in args.wires.iter() {
+        println!("wire {wire} = {}", state.read_wire(*wire));
+    }
+
+    Ok(finished)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("Simulation didn't finish");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
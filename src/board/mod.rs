@@ -5,7 +5,7 @@ use std::{
     num::NonZeroU32,
     ops::Deref,
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
 };
@@ -17,30 +17,64 @@ use eframe::{
 use emath::{vec2, Align2, Pos2, Rect};
 
 use crate::{
+    breakpoint::Breakpoint,
     circuits::{
         props::{CircuitPropertyImpl, CircuitPropertyStore},
         Circuit, CircuitNode, CircuitPin, CircuitPinId, CircuitPreview, CircuitStateContext,
     },
+    compiled::CompiledRegions,
     containers::{Chunks2D, ChunksLookaround, FixedVec},
-    state::{State, StateCollection, WireState},
+    state::{ActivityProfile, State, StateCollection, WireState},
     unwrap_option_or_continue, unwrap_option_or_return,
     vector::{IsZero, Vec2f, Vec2i, Vec2isize, Vec2u},
-    wires::{FoundWireNode, TileWires, Wire, WireNode, WirePart, WirePoint},
-    ArcString, Direction2, Direction4, PaintContext, PastePreview, RwLock, Screen,
+    wires::{FoundWireNode, TileWires, Wire, WireNode, WirePart, WirePoint, WirePoints},
+    ArcString, Direction2, Direction4, Mutex, PaintContext, PastePreview, RwLock, Screen,
 };
 
 use self::selection::{SelectedWorldObject, Selection};
 
 pub mod selection;
 
+/// Blocks simulation while board is modified
+#[derive(Default)]
+pub struct SimLock {
+    lock: RwLock<()>,
+    /// Incremented on every write, so data derived from board layout knows to rebuild
+    generation: AtomicU64,
+}
+
+impl SimLock {
+    #[track_caller]
+    pub fn read(&self) -> impl Sized + '_ {
+        self.lock.read()
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> impl Sized + '_ {
+        let guard = self.lock.write();
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        guard
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+}
+
 pub struct CircuitBoard {
     pub wires: FixedVec<Wire>,
     pub circuits: FixedVec<Circuit>,
     pub states: StateCollection,
 
-    // RwLock for blocking simulation while modifying board
-    pub sim_lock: Arc<RwLock<()>>,
+    pub sim_lock: Arc<SimLock>,
     ordered_queue: bool,
+
+    compiled: bool,
+    /// Built on demand while compiled evaluation is enabled
+    compiled_regions: Mutex<Option<Arc<CompiledRegions>>>,
+
+    /// Shared with every state of this board
+    pub breakpoints: Arc<RwLock<Vec<Breakpoint>>>,
 }
 
 impl CircuitBoard {
@@ -51,6 +85,9 @@ impl CircuitBoard {
             states: StateCollection::new(),
             sim_lock: Default::default(),
             ordered_queue: false,
+            compiled: false,
+            compiled_regions: Default::default(),
+            breakpoints: Default::default(),
         }
     }
 
@@ -134,7 +171,7 @@ impl CircuitBoard {
             }
         }
 
-        let mut new_points = HashMap::new();
+        let mut new_points = WirePoints::default();
         for pos in points.iter() {
             let mut point = unwrap_option_or_continue!(wire.points.remove(pos));
 
@@ -164,6 +201,7 @@ impl CircuitBoard {
     pub fn save(&self) -> crate::io::CircuitBoardData {
         let sim_lock = self.sim_lock.write();
         let data = crate::io::CircuitBoardData {
+            version: crate::io::CircuitBoardData::VERSION,
             wires: self
                 .wires
                 .inner()
@@ -185,6 +223,8 @@ impl CircuitBoard {
                 .map(|s| s.as_ref().map(|s| s.save()))
                 .collect(),
             ordered: self.ordered_queue,
+            compiled: self.compiled,
+            breakpoints: self.breakpoints.read().clone(),
         };
         drop(sim_lock);
         data
@@ -200,7 +240,11 @@ impl CircuitBoard {
                 .enumerate()
                 .map(|(i, c)| {
                     c.as_ref().and_then(|c| {
-                        let preview = ctx.get_circuit_preview(&c.ty)?;
+                        let ty = crate::io::CircuitBoardData::migrate_circuit_type(
+                            data.version,
+                            &c.ty,
+                        );
+                        let preview = ctx.get_circuit_preview(&ty)?;
                         let props = preview.imp.default_props();
                         props.load(&c.props);
                         let circ = Circuit::create(i, c.pos, preview, Some(props));
@@ -237,6 +281,9 @@ impl CircuitBoard {
             states: StateCollection::new(),
             sim_lock: Default::default(),
             ordered_queue: data.ordered,
+            compiled: data.compiled,
+            compiled_regions: Default::default(),
+            breakpoints: Arc::new(RwLock::new(data.breakpoints.clone())),
         };
         let board = Arc::new(RwLock::new(board));
 
@@ -263,6 +310,37 @@ impl CircuitBoard {
         drop(sim_lock);
     }
 
+    pub fn is_compiled(&self) -> bool {
+        self.compiled
+    }
+
+    /// Enables levelized evaluation of acyclic regions of combinational circuits.
+    /// A wire change then updates every compiled circuit it affects in one pass,
+    /// while other circuits and feedback loops still go through the event queue.
+    /// Propagation delay only applies where signals leave a region
+    pub fn set_compiled(&mut self, compiled: bool, lock_sim: bool) {
+        let sim_lock = lock_sim.then(|| self.sim_lock.write());
+        self.compiled = compiled;
+        drop(sim_lock);
+    }
+
+    /// Regions of current board layout, None if compiled evaluation is disabled
+    pub fn compiled_regions(&self) -> Option<Arc<CompiledRegions>> {
+        if !self.compiled {
+            return None;
+        }
+        let generation = self.sim_lock.generation();
+        let mut regions = self.compiled_regions.lock();
+        if let Some(regions) = &*regions {
+            if regions.generation == generation {
+                return Some(regions.clone());
+            }
+        }
+        let built = Arc::new(CompiledRegions::build(self, generation));
+        *regions = Some(built.clone());
+        Some(built)
+    }
+
     // Run board simulation after loading. Not required on newly created or empty boards
     #[cfg(not(feature = "single_thread"))]
     pub fn activate(&self) {
@@ -309,6 +387,8 @@ impl SelectedItem {
 pub struct ActiveCircuitBoard {
     pub board: Arc<RwLock<CircuitBoard>>,
     pub state: Arc<State>,
+    /// Id of [`ActiveCircuitBoard::state`] in board's [`StateCollection`]
+    pub state_id: usize,
 
     pub wire_nodes: Chunks2D<16, WireNode>,
     pub circuit_nodes: Chunks2D<16, CircuitNode>,
@@ -317,16 +397,26 @@ pub struct ActiveCircuitBoard {
     pub selection: RefCell<Selection>,
 
     pub wires_drawn: AtomicUsize,
+
+    /// Wires that didn't settle, updated every frame
+    oscillating_wires: HashSet<usize>,
+
+    /// Tint circuits and wires by how much work they cause
+    pub heat_map: bool,
+    /// Profile and its maximum circuit and wire counts, updated every frame heat map is shown
+    heat_profile: Option<(ActivityProfile, u64, u64)>,
 }
 
 impl ActiveCircuitBoard {
     pub const WIRE_THICKNESS: f32 = 0.2;
     pub const WIRE_POINT_THICKNESS: f32 = 0.35;
+    pub const OSCILLATION_HIGHLIGHT: Color32 = Color32::from_rgb(255, 0, 255);
+    pub const HEAT_COLOR: Color32 = Color32::from_rgb(255, 60, 0);
 
-    pub fn new(board: Arc<RwLock<CircuitBoard>>, state: usize) -> Option<Self> {
+    pub fn new(board: Arc<RwLock<CircuitBoard>>, state_id: usize) -> Option<Self> {
         let state = {
             let board = board.read();
-            board.states.get(state)?
+            board.states.get(state_id)?
         };
 
         let (wires, circuits) = {
@@ -393,15 +483,37 @@ impl ActiveCircuitBoard {
             wire_nodes: wires,
             circuit_nodes: circuits,
             state,
+            state_id,
             wire_drag_pos: None,
             selection: RefCell::new(Selection::new()),
 
             wires_drawn: AtomicUsize::new(0),
+            oscillating_wires: HashSet::new(),
+            heat_map: false,
+            heat_profile: None,
         })
     }
 
+    /// Switches to another state of the same board, returns false if it doesn't exist
+    pub fn set_state(&mut self, state_id: usize) -> bool {
+        let state = self.board.read().states.get(state_id);
+        let state = unwrap_option_or_return!(state, false);
+        // Only the shown state is profiled
+        self.state.set_profiling(false);
+        self.state = state;
+        self.state_id = state_id;
+        self.oscillating_wires.clear();
+        true
+    }
+
     pub fn update(&mut self, ctx: &PaintContext, selected: SelectedItem, debug: bool) {
         self.wires_drawn.store(0, Ordering::Relaxed);
+        self.oscillating_wires = self.state.oscillating_wires();
+        self.heat_profile = self.heat_map.then(|| {
+            let profile = self.state.profile();
+            let (circuits, wires) = (profile.max_circuit_updates(), profile.max_wire_changes());
+            (profile, circuits, wires)
+        });
         self.selection
             .borrow_mut()
             .pre_update_selection(self, ctx, selected.selection());
@@ -732,6 +844,14 @@ impl ActiveCircuitBoard {
 
     /* #region Drawing nodes */
 
+    /// Heat map outline of a wire, None if heat map is hidden or wire didn't change
+    fn wire_heat_color(&self, wire: usize) -> Option<Color32> {
+        let (profile, _, max) = self.heat_profile.as_ref()?;
+        let count = profile.wire_changes.get(&wire).copied().unwrap_or(0);
+        let heat = ActivityProfile::heat(count, *max);
+        (heat > 0.0).then(|| Self::HEAT_COLOR.linear_multiply(heat))
+    }
+
     fn draw_wire_node(
         &self,
         ctx: &PaintContext<'_>,
@@ -747,6 +867,7 @@ impl ActiveCircuitBoard {
             dir: Direction2,
             pos: Vec2i,
             color: Color32,
+            highlight: Option<Color32>,
         }
 
         fn draw_wire(info: WireDrawInfo, this: &ActiveCircuitBoard, ctx: &PaintContext) {
@@ -790,6 +911,14 @@ impl ActiveCircuitBoard {
             };
 
             this.draw_wire_part(ctx, &part, info.color);
+            if let Some(highlight) = info.highlight {
+                let rect = ActiveCircuitBoard::calc_wire_part_rect(&ctx.screen, &part);
+                ctx.paint.rect_stroke(
+                    rect.expand(ctx.screen.scale * 0.1),
+                    Rounding::none(),
+                    Stroke::new(ctx.screen.scale * 0.1, highlight),
+                );
+            }
         }
         if node.is_empty() {
             return;
@@ -799,6 +928,7 @@ impl ActiveCircuitBoard {
         let center_color = node.wire.get().map(|w| self.state.read_wire(w).color());
 
         for dir in Direction2::iter_all() {
+            let wire = node.wire.get().or_else(|| wires.dir(dir.into()));
             let wire_color = center_color.or_else(|| {
                 wires
                     .dir(dir.into())
@@ -806,6 +936,10 @@ impl ActiveCircuitBoard {
             });
 
             let wire_color = unwrap_option_or_continue!(wire_color);
+            let highlight = wire.and_then(|w| match self.oscillating_wires.contains(&w) {
+                true => Some(Self::OSCILLATION_HIGHLIGHT),
+                false => self.wire_heat_color(w),
+            });
 
             let next_node_rel_pos = dir.unit_vector(false).convert(|v| v as isize);
             let next_node = lookaround.get_relative(next_node_rel_pos);
@@ -817,6 +951,7 @@ impl ActiveCircuitBoard {
                 dir,
                 pos,
                 color: wire_color,
+                highlight,
             };
 
             draw_wire(draw, self, ctx);
@@ -1010,6 +1145,15 @@ impl ActiveCircuitBoard {
 
         imp.draw(&state_ctx, &circ_ctx);
 
+        if let Some((profile, max, _)) = &self.heat_profile {
+            let count = profile.circuit_updates.get(&circ_id).copied().unwrap_or(0);
+            let heat = ActivityProfile::heat(count, *max);
+            if heat > 0.0 {
+                let color = Self::HEAT_COLOR.linear_multiply(heat * 0.6);
+                circ_ctx.paint.rect_filled(rect, Rounding::none(), color);
+            }
+        }
+
         let name = circuit.props.read("name", |s: &ArcString| s.get_arc());
         let label_dir = circuit.props.read_clone::<Direction4>("label_dir");
 
@@ -1887,6 +2031,11 @@ impl ActiveCircuitBoard {
         }
     }
 
+    /// Id of the wire going from `pos` in `dir` direction
+    pub fn wire_at(&self, pos: Vec2i, dir: Direction4) -> Option<usize> {
+        self.find_wire_node(pos, dir).map(|node| node.wire)
+    }
+
     fn find_wire_node(&self, pos: Vec2i, dir: Direction4) -> Option<FoundWireNode> {
         let node = self.wire_nodes.get(pos.convert(|v| v as isize))?;
         self.find_wire_node_from_node(node, pos, dir)
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    state::{SimTime, UpdateTask, WireState},
+    DynStaticStr,
+};
+
+/// What a [`Breakpoint`] watches
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum BreakpointTarget {
+    Wire(usize),
+    /// Pin is referenced by name, so breakpoint survives pins being recreated
+    Pin {
+        circuit: usize,
+        pin: DynStaticStr,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum BreakpointCondition {
+    Changes,
+    Becomes(WireState),
+}
+
+impl BreakpointCondition {
+    pub fn matches(self, old: WireState, new: WireState) -> bool {
+        match self {
+            BreakpointCondition::Changes => old != new,
+            BreakpointCondition::Becomes(state) => old != state && new == state,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Breakpoint {
+    pub target: BreakpointTarget,
+    pub condition: BreakpointCondition,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Breakpoint {
+    pub fn new(target: BreakpointTarget) -> Self {
+        Self {
+            target,
+            condition: BreakpointCondition::Changes,
+            enabled: true,
+        }
+    }
+}
+
+/// What committed the value that hit a breakpoint
+#[derive(Clone, Copy, Debug)]
+pub enum HitSource {
+    Task(UpdateTask),
+    /// Value was set outside of simulation queue, e.g. by user input or reset
+    External,
+}
+
+/// Breakpoint that paused simulation, with the value change that triggered it
+#[derive(Clone, Debug)]
+pub struct BreakpointHit {
+    pub breakpoint: Breakpoint,
+    pub source: HitSource,
+    pub time: SimTime,
+    pub old: WireState,
+    pub new: WireState,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        let becomes_true = BreakpointCondition::Becomes(WireState::True);
+        assert!(becomes_true.matches(WireState::False, WireState::True));
+        assert!(becomes_true.matches(WireState::None, WireState::True));
+        assert!(!becomes_true.matches(WireState::True, WireState::True));
+        assert!(!becomes_true.matches(WireState::True, WireState::False));
+
+        assert!(BreakpointCondition::Changes.matches(WireState::True, WireState::Error));
+        assert!(!BreakpointCondition::Changes.matches(WireState::None, WireState::None));
+    }
+}
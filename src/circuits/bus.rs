@@ -0,0 +1,169 @@
+use eframe::epaint::{Color32, FontId, Rounding, Stroke};
+use emath::Align2;
+
+use crate::{circuits::*, Direction4};
+
+use super::props::CircuitProperty;
+
+struct Circuit {
+    bus: CircuitPinInfo,
+    bits: Box<[CircuitPinInfo]>,
+    join: bool,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right, 8, false);
+        Self {
+            bus: description.pins[0].to_info(),
+            bits: description.pins[1..].iter().map(|p| p.to_info()).collect(),
+            join: false,
+        }
+    }
+
+    fn draw(width: u32, ctx: &PaintContext, semi_transparent: bool) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = Color32::from_gray(200).linear_multiply(opacity);
+
+        ctx.paint.rect(
+            ctx.rect.shrink(ctx.screen.scale * 0.25),
+            Rounding::same(ctx.screen.scale * 0.25),
+            fill_color,
+            Stroke::new(0.15 * ctx.screen.scale, border_color),
+        );
+
+        let font = FontId::monospace(ctx.screen.scale * 0.6);
+        ctx.paint.text(
+            ctx.rect.center(),
+            Align2::CENTER_CENTER,
+            width.to_string(),
+            font,
+            border_color,
+        );
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> DynCircuitDescription {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        let width = read_width_prop(props).max(2);
+        let join = props.read_clone("join").unwrap_or(false);
+        Self::describe(dir, width, join)
+    }
+
+    fn describe(dir: Direction4, width: u32, join: bool) -> DynCircuitDescription {
+        let (bus_dir, bit_dir) = match join {
+            false => (InternalPinDirection::Inside, InternalPinDirection::Outside),
+            true => (InternalPinDirection::Outside, InternalPinDirection::Inside),
+        };
+
+        let bus = CircuitPinDescription {
+            display_name: "Bus".into(),
+            display_dir: Some(Direction4::Left),
+            dir: bus_dir,
+            name: "bus".into(),
+            pos: [0, 0].into(),
+            width,
+        };
+        let bits = (0..width).map(|i| CircuitPinDescription {
+            display_name: i.to_string().into(),
+            display_dir: Some(Direction4::Right),
+            dir: bit_dir,
+            name: format!("bit_{i}").into(),
+            pos: [1, i].into(),
+            width: 1,
+        });
+
+        describe_directional_dyn(
+            Direction4::Right,
+            dir,
+            [2, width],
+            std::iter::once(bus).chain(bits),
+        )
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, _: &CircuitStateContext, paint_ctx: &PaintContext) {
+        Circuit::draw(self.bus.width, paint_ctx, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Self::describe_props(props);
+        self.bus = description.pins[0].to_info();
+        self.bits = description.pins[1..].iter().map(|p| p.to_info()).collect();
+
+        let mut vec = vec![self.bus.clone()];
+        vec.extend(self.bits.iter().cloned());
+        vec.into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        if self.join {
+            let bits = self.bits.iter().map(|b| b.get_state(state_ctx));
+            let state = WireState::from_bits(self.bus.width, bits);
+            self.bus.set_state(state_ctx, state);
+        } else {
+            let state = self.bus.get_state(state_ctx);
+            for (i, bit) in self.bits.iter().enumerate() {
+                bit.set_state(state_ctx, state.bit(i as u32));
+            }
+        }
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" | "width" => (true, true),
+            "join" => (false, true),
+            _ => (false, false),
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.join = props.read_clone("join").unwrap_or(false);
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "bus_splitter".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        Circuit::draw(read_width_prop(props).max(2), ctx, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("width", "Width", 8u32),
+            CircuitProperty::new("join", "Join bits", false),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "Bus splitter".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props)
+    }
+}
@@ -74,13 +74,7 @@ impl CircuitImpl for Circuit {
     fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
         Self::draw(Some(state_ctx), paint_ctx, false);
 
-        // HACK: write proper circuit interactables
-        let rect = paint_ctx.rect.expand(paint_ctx.screen.scale * -0.75);
-        let interaction = paint_ctx.ui.interact(
-            rect,
-            paint_ctx.ui.auto_id_with(state_ctx.circuit.pos),
-            Sense::drag(),
-        );
+        let interaction = state_ctx.interact(paint_ctx, Sense::drag());
         let shift = paint_ctx.egui_ctx.input(|input| input.modifiers.shift);
         if interaction.drag_started_by(PointerButton::Primary)
             || !shift && interaction.drag_released_by(PointerButton::Primary)
@@ -0,0 +1,303 @@
+use eframe::epaint::{Color32, PathShape, Rounding, Stroke};
+use emath::{pos2, vec2, Rect};
+
+use crate::{
+    circuits::{props::CircuitProperty, *},
+    describe_directional_circuit,
+    state::to_duration,
+    Direction4,
+};
+
+/// Square wave timing, in simulation time units
+#[derive(Clone, Copy)]
+struct Timing {
+    high: SimTime,
+    low: SimTime,
+    phase: SimTime,
+}
+
+impl Timing {
+    /// Half of one second, so default clock blinks visibly in real time
+    const DEFAULT_HALF_PERIOD: u32 = 500_000_000;
+
+    fn from_props(props: &CircuitPropertyStore) -> Self {
+        // Zero-length half periods would make the clock stuck at one time
+        let read = |id| {
+            let time = props.read_clone::<u32>(id).unwrap_or(Self::DEFAULT_HALF_PERIOD);
+            time.max(1) as SimTime
+        };
+        Self {
+            high: read("high"),
+            low: read("low"),
+            phase: props.read_clone::<u32>("phase").unwrap_or(0) as SimTime,
+        }
+    }
+
+    fn period(self) -> SimTime {
+        self.high + self.low
+    }
+
+    /// Position within period at `time`, period starts with the rising edge
+    fn position(self, time: SimTime) -> SimTime {
+        (time % self.period() + self.phase % self.period()) % self.period()
+    }
+
+    fn value(self, time: SimTime) -> bool {
+        self.position(time) < self.high
+    }
+
+    /// Time left until the output changes
+    fn next_edge(self, time: SimTime) -> SimTime {
+        let position = self.position(time);
+        match position < self.high {
+            true => self.high - position,
+            false => self.period() - position,
+        }
+    }
+}
+
+struct Circuit {
+    timing: Timing,
+    output: CircuitPinInfo,
+    enable: CircuitPinInfo,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            timing: Timing {
+                high: Timing::DEFAULT_HALF_PERIOD as SimTime,
+                low: Timing::DEFAULT_HALF_PERIOD as SimTime,
+                phase: 0,
+            },
+            output: description.pins[0].to_info(),
+            enable: description.pins[1].to_info(),
+        }
+    }
+
+    /// `position` is the time within period, None while disabled and for previews
+    fn draw(
+        ctx: &PaintContext,
+        timing: Timing,
+        position: Option<SimTime>,
+        output: WireState,
+        semi_transparent: bool,
+    ) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = Color32::from_gray(200).linear_multiply(opacity);
+        let wave_color = match position {
+            Some(_) => output.color(),
+            None => Color32::from_gray(100),
+        }
+        .linear_multiply(opacity);
+
+        let body = ctx.rect.expand(ctx.screen.scale * -0.5);
+        ctx.paint.rect(
+            body,
+            Rounding::same(ctx.screen.scale * 0.15),
+            fill_color,
+            Stroke::new(0.15 * ctx.screen.scale, border_color),
+        );
+
+        let wave = Rect::from_min_size(
+            body.left_top() + body.size() * vec2(0.15, 0.3),
+            body.size() * vec2(0.7, 0.4),
+        );
+        let high_width = wave.width() * timing.high as f32 / timing.period() as f32;
+        let points = vec![
+            wave.left_bottom(),
+            wave.left_top(),
+            pos2(wave.left() + high_width, wave.top()),
+            pos2(wave.left() + high_width, wave.bottom()),
+            wave.right_bottom(),
+        ];
+        ctx.paint.add(PathShape::line(
+            points,
+            Stroke::new(0.1 * ctx.screen.scale, wave_color),
+        ));
+
+        if let Some(position) = position {
+            let x = wave.left() + wave.width() * position as f32 / timing.period() as f32;
+            ctx.paint.line_segment(
+                [pos2(x, body.top()), pos2(x, body.bottom())],
+                Stroke::new(0.1 * ctx.screen.scale, border_color),
+            );
+        }
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<2> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<2> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [3, 3],
+
+            "out": Outside, "Out", Right, [2, 1],
+            "en": Inside, "Enable", Down, [1, 2]
+        }
+    }
+
+    /// Unconnected enable pin leaves the clock running
+    fn enabled(&self, state_ctx: &CircuitStateContext) -> Option<bool> {
+        match self.enable.get_state(state_ctx) {
+            WireState::True | WireState::None => Some(true),
+            WireState::False => Some(false),
+            WireState::Error | WireState::Bus(_) => None,
+        }
+    }
+
+    fn update_output(&self, state_ctx: &CircuitStateContext) {
+        let output = match self.enabled(state_ctx) {
+            Some(true) => self.timing.value(state_ctx.global_state.sim_time()).into(),
+            Some(false) => WireState::False,
+            None => WireState::Error,
+        };
+        self.output.set_state(state_ctx, output);
+    }
+
+    fn next_update(&self, state_ctx: &CircuitStateContext) -> Option<Duration> {
+        let time = state_ctx.global_state.sim_time();
+        (self.enabled(state_ctx) == Some(true)).then(|| to_duration(self.timing.next_edge(time)))
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let position = (self.enabled(state_ctx) == Some(true))
+            .then(|| self.timing.position(state_ctx.global_state.sim_time()));
+        let output = self.output.get_state(state_ctx);
+        Circuit::draw(paint_ctx, self.timing, position, output, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.output = description.pins[0].to_info();
+        self.enable = description.pins[1].to_info();
+        vec![self.output.clone(), self.enable.clone()].into_boxed_slice()
+    }
+
+    /// Also runs after props change, so edited timing reschedules the pending edge
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        self.update_output(state_ctx);
+        state_ctx.set_update_interval(self.next_update(state_ctx));
+    }
+
+    fn update(&self, state_ctx: &CircuitStateContext) {
+        self.update_output(state_ctx);
+    }
+
+    fn init_state(&self, state_ctx: &CircuitStateContext) {
+        state_ctx.set_update_interval(self.next_update(state_ctx));
+    }
+
+    fn update_interval(&self, state_ctx: &CircuitStateContext) -> Option<Duration> {
+        self.next_update(state_ctx)
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *resize = true;
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.timing = Timing::from_props(props);
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "clock".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        Circuit::draw(
+            ctx,
+            Timing::from_props(props),
+            None,
+            WireState::False,
+            in_world,
+        );
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("high", "High time (ns)", Timing::DEFAULT_HALF_PERIOD),
+            CircuitProperty::new("low", "Low time (ns)", Timing::DEFAULT_HALF_PERIOD),
+            CircuitProperty::new("phase", "Phase (ns)", 0u32),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "Clock".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::{
+        test::{init_state, load_board, pin_state},
+        SimulationStep, WireState,
+    };
+
+    #[test]
+    fn timing_change() {
+        let board = load_board(
+            r#"(
+            wires: [],
+            circuits: [
+                Some((ty: "clock", pos: [0, 0], pin_wires: [],
+                    props: ({"high": 1000, "low": 1000}))),
+            ],
+            states: [],
+        )"#,
+        );
+        let state = init_state(&board);
+        state.step(SimulationStep::Ticks(50));
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::True);
+
+        // Running clock picks up new timing right away, not at its previously scheduled edge
+        {
+            let board = board.read();
+            let circuit = board.circuits.get(0).unwrap();
+            circuit.props.write("high", |high: &mut u32| *high = 100);
+            circuit.imp.write().apply_props(&circuit.props, Some("high"));
+        }
+        state.update_circuit_signals(0, None);
+        state.step(SimulationStep::Ticks(100));
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::False);
+        state.step(SimulationStep::Ticks(1000));
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::True);
+    }
+}
@@ -0,0 +1,159 @@
+use eframe::epaint::{Color32, FontId, Rounding, Stroke};
+use emath::{vec2, Align2, Rect};
+
+use crate::{
+    circuits::{props::CircuitProperty, *},
+    describe_directional_circuit,
+    vector::Vec2f,
+    Direction4,
+};
+
+struct Circuit {
+    output: CircuitPinInfo,
+    value: bool,
+    width: u32,
+}
+
+impl Circuit {
+    fn new(value: bool) -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            output: description.pins[0].to_info(),
+            value,
+            width: 1,
+        }
+    }
+
+    fn draw(ctx: &PaintContext, angle: f32, value: bool, semi_transparent: bool) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+
+        let center = ctx
+            .rect
+            .lerp_inside(Vec2f::from([0.25, 0.5]).rotated_xy(angle, 0.5).into());
+        let rect = Rect::from_center_size(center, vec2(0.8, 0.8) * ctx.screen.scale);
+        ctx.paint.rect(
+            rect,
+            Rounding::same(ctx.screen.scale * 0.1),
+            Color32::from_gray(200).linear_multiply(opacity),
+            Stroke::new(
+                0.1 * ctx.screen.scale,
+                WireState::from(value).color().linear_multiply(opacity),
+            ),
+        );
+        ctx.paint.text(
+            center,
+            Align2::CENTER_CENTER,
+            if value { "1" } else { "0" },
+            FontId::monospace(ctx.screen.scale * 0.6),
+            Color32::BLACK.linear_multiply(opacity),
+        );
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<1> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir).with_width(read_width_prop(props))
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<1> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [2, 1],
+
+            "out": Outside, "Out", Right, [1, 0]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let angle = state_ctx
+            .props()
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        Circuit::draw(paint_ctx, angle, self.value, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.output = description.pins[0].to_info();
+        vec![self.output.clone()].into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        let bits = std::iter::repeat(WireState::from(self.value));
+        self.output
+            .set_state(state_ctx, WireState::from_bits(self.width, bits));
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" => (true, true),
+            "width" => (false, true),
+            _ => (false, false),
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.width = read_width_prop(props);
+    }
+}
+
+/// Strongly drives every bit of its wire high or low
+#[derive(Debug)]
+pub struct Preview {
+    pub value: bool,
+}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        match self.value {
+            true => "const_high".into(),
+            false => "const_low".into(),
+        }
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let angle = props
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        Circuit::draw(ctx, angle, self.value, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new(self.value))
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview { value: self.value }))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("width", "Width", 1u32),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        match self.value {
+            true => "Constant high".into(),
+            false => "Constant low".into(),
+        }
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
@@ -0,0 +1,669 @@
+use eframe::{
+    egui::{ComboBox, Ui},
+    epaint::{Color32, FontId, PathShape, Rounding, Stroke},
+};
+use emath::{vec2, Align2, Pos2};
+
+use crate::{circuits::*, vector::Vec2f, Direction4};
+
+use super::props::{ActiveLevel, CircuitProperty, CircuitPropertyImpl};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    SrLatch,
+    DLatch,
+    DFlipFlop,
+    JkFlipFlop,
+    TFlipFlop,
+}
+
+impl Kind {
+    fn type_name(self) -> &'static str {
+        match self {
+            Kind::SrLatch => "sr_latch",
+            Kind::DLatch => "d_latch",
+            Kind::DFlipFlop => "d_flip_flop",
+            Kind::JkFlipFlop => "jk_flip_flop",
+            Kind::TFlipFlop => "t_flip_flop",
+        }
+    }
+
+    fn display_name(self) -> &'static str {
+        match self {
+            Kind::SrLatch => "SR latch",
+            Kind::DLatch => "D latch",
+            Kind::DFlipFlop => "D flip-flop",
+            Kind::JkFlipFlop => "JK flip-flop",
+            Kind::TFlipFlop => "T flip-flop",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::SrLatch => "SR",
+            Kind::DLatch | Kind::DFlipFlop => "D",
+            Kind::JkFlipFlop => "JK",
+            Kind::TFlipFlop => "T",
+        }
+    }
+
+    /// Name and display name of data inputs
+    fn data_pins(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Kind::SrLatch => &[("s", "S"), ("r", "R")],
+            Kind::DLatch | Kind::DFlipFlop => &[("d", "D")],
+            Kind::JkFlipFlop => &[("j", "J"), ("k", "K")],
+            Kind::TFlipFlop => &[("t", "T")],
+        }
+    }
+
+    /// Name and display name of clock or enable input
+    fn clock_pin(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Kind::SrLatch => None,
+            Kind::DLatch => Some(("en", "Enable")),
+            Kind::DFlipFlop | Kind::JkFlipFlop | Kind::TFlipFlop => Some(("clk", "Clock")),
+        }
+    }
+
+    fn is_edge_triggered(self) -> bool {
+        matches!(self, Kind::DFlipFlop | Kind::JkFlipFlop | Kind::TFlipFlop)
+    }
+}
+
+/// Clock transition flip-flops change state on
+#[derive(Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+enum Edge {
+    #[default]
+    Rising,
+    Falling,
+}
+
+// Saved by name, like ActiveLevel, so it loads back from RON
+impl Serialize for Edge {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.name().serialize(serializer)
+    }
+}
+
+impl Edge {
+    fn name(self) -> &'static str {
+        match self {
+            Edge::Rising => "Rising",
+            Edge::Falling => "Falling",
+        }
+    }
+
+    fn matches(self, old: WireState, new: WireState) -> bool {
+        match self {
+            Edge::Rising => old == WireState::False && new == WireState::True,
+            Edge::Falling => old == WireState::True && new == WireState::False,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    /// Stored value, None until first update sets it to the initial value
+    q: WireState,
+    /// Clock pin state seen by the last update, for edge detection
+    clock: WireState,
+}
+
+impl InternalCircuitState for State {
+    fn serialize(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap()
+    }
+}
+
+/// Control inputs read floating as low. None for invalid states
+fn read_bool(state: WireState) -> Option<bool> {
+    match state {
+        WireState::True => Some(true),
+        WireState::False | WireState::None => Some(false),
+        WireState::Error | WireState::Bus(_) => None,
+    }
+}
+
+fn invert(state: WireState) -> WireState {
+    match state {
+        WireState::True => WireState::False,
+        WireState::False => WireState::True,
+        _ => WireState::Error,
+    }
+}
+
+const SIZE: [u32; 2] = [4, 5];
+
+struct Pins {
+    data: Box<[CircuitPinInfo]>,
+    clock: Option<CircuitPinInfo>,
+    q: CircuitPinInfo,
+    nq: CircuitPinInfo,
+    set: Option<CircuitPinInfo>,
+    reset: Option<CircuitPinInfo>,
+}
+
+impl Pins {
+    /// Splits pins in the order `Circuit::describe` creates them
+    fn new(kind: Kind, description: &DynCircuitDescription) -> Self {
+        let mut pins = description.pins.iter().map(|p| p.to_info());
+        Self {
+            data: pins.by_ref().take(kind.data_pins().len()).collect(),
+            clock: kind.clock_pin().and_then(|_| pins.next()),
+            q: pins.next().expect("q pin"),
+            nq: pins.next().expect("nq pin"),
+            set: pins.next(),
+            reset: pins.next(),
+        }
+    }
+
+    fn all(&self) -> impl Iterator<Item = &CircuitPinInfo> {
+        self.data
+            .iter()
+            .chain(&self.clock)
+            .chain([&self.q, &self.nq])
+            .chain(&self.set)
+            .chain(&self.reset)
+    }
+}
+
+struct Circuit {
+    kind: Kind,
+    pins: Pins,
+    edge: Edge,
+    level: ActiveLevel,
+    initial: bool,
+}
+
+impl Circuit {
+    fn new(kind: Kind) -> Self {
+        let description = Self::describe(kind, Direction4::Right, false);
+        Self {
+            kind,
+            pins: Pins::new(kind, &description),
+            edge: Edge::Rising,
+            level: ActiveLevel::High,
+            initial: false,
+        }
+    }
+
+    fn draw(ctx: &PaintContext, kind: Kind, angle: f32, q: WireState, semi_transparent: bool) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = Color32::from_gray(200).linear_multiply(opacity);
+
+        let size = vec2(SIZE[0] as f32, SIZE[1] as f32);
+        let transformer = |x: f32, y: f32| -> Pos2 {
+            ctx.rect
+                .lerp_inside(Vec2f::from(vec2(x, y) / size).rotated_xy(angle, 0.5).into())
+        };
+
+        ctx.paint.rect(
+            ctx.rect.shrink(ctx.screen.scale * 0.5),
+            Rounding::same(ctx.screen.scale * 0.15),
+            fill_color,
+            Stroke::new(0.15 * ctx.screen.scale, border_color),
+        );
+
+        if kind.is_edge_triggered() {
+            let clock_y = if kind.data_pins().len() == 2 {
+                2.5
+            } else {
+                3.5
+            };
+            ctx.paint.add(PathShape::closed_line(
+                vec![
+                    transformer(0.5, clock_y - 0.3),
+                    transformer(0.9, clock_y),
+                    transformer(0.5, clock_y + 0.3),
+                ],
+                Stroke::new(0.1 * ctx.screen.scale, border_color),
+            ));
+        }
+
+        ctx.paint.text(
+            transformer(2.0, 2.5),
+            Align2::CENTER_CENTER,
+            kind.label(),
+            FontId::monospace(ctx.screen.scale * 0.8),
+            border_color,
+        );
+        ctx.paint.circle_filled(
+            transformer(3.0, 1.5),
+            ctx.screen.scale * 0.2,
+            q.color().linear_multiply(opacity),
+        );
+    }
+
+    fn describe_props(kind: Kind, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        let async_pins = props.read_clone("async").unwrap_or(false);
+        Self::describe(kind, dir, async_pins)
+    }
+
+    fn describe(kind: Kind, dir: Direction4, async_pins: bool) -> DynCircuitDescription {
+        let pin =
+            |name: &'static str, display_name: &'static str, dir, display_dir, pos: [u32; 2]| {
+                CircuitPinDescription {
+                    display_name: display_name.into(),
+                    display_dir: Some(display_dir),
+                    dir,
+                    name: name.into(),
+                    pos: pos.into(),
+                    width: 1,
+                }
+            };
+        let input = InternalPinDirection::Inside;
+        let output = InternalPinDirection::Outside;
+
+        let data = kind.data_pins();
+        let data_rows: &[u32] = if data.len() == 2 { &[1, 3] } else { &[1] };
+        let clock_row = if data.len() == 2 { 2 } else { 3 };
+
+        let mut pins: Vec<_> = data
+            .iter()
+            .zip(data_rows)
+            .map(|((name, display), row)| pin(name, display, input, Direction4::Left, [0, *row]))
+            .collect();
+        if let Some((name, display)) = kind.clock_pin() {
+            pins.push(pin(name, display, input, Direction4::Left, [0, clock_row]));
+        }
+        pins.push(pin("q", "Q", output, Direction4::Right, [3, 1]));
+        pins.push(pin("nq", "!Q", output, Direction4::Right, [3, 3]));
+        if async_pins {
+            pins.push(pin("set", "Set", input, Direction4::Up, [2, 0]));
+            pins.push(pin("reset", "Reset", input, Direction4::Down, [2, 4]));
+        }
+
+        describe_directional_dyn(Direction4::Right, dir, SIZE, pins)
+    }
+
+    /// Next stored value, None if it doesn't change
+    fn next_value(
+        &self,
+        data: &[Option<bool>],
+        q: WireState,
+        clock: WireState,
+        prev_clock: WireState,
+    ) -> Option<WireState> {
+        let triggered = match self.kind {
+            Kind::SrLatch => true,
+            // Undefined enable might be letting data through, so stored value is unknown
+            Kind::DLatch => match self.level.is_active(clock) {
+                Some(active) => active,
+                None => return Some(WireState::Error),
+            },
+            _ => self.edge.matches(prev_clock, clock),
+        };
+        if !triggered {
+            return None;
+        }
+        if data.contains(&None) {
+            return Some(WireState::Error);
+        }
+        let data: Vec<bool> = data.iter().flatten().copied().collect();
+
+        match (self.kind, data.as_slice()) {
+            (Kind::SrLatch, [true, true]) => Some(WireState::Error),
+            (Kind::SrLatch | Kind::JkFlipFlop, [false, false]) => None,
+            (Kind::SrLatch | Kind::JkFlipFlop, [true, false]) => Some(WireState::True),
+            (Kind::SrLatch | Kind::JkFlipFlop, [false, true]) => Some(WireState::False),
+            (Kind::JkFlipFlop, [true, true]) | (Kind::TFlipFlop, [true]) => Some(invert(q)),
+            (Kind::TFlipFlop, [false]) => None,
+            (_, [d]) => Some((*d).into()),
+            _ => None,
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let angle = state_ctx
+            .props()
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        let q = self.pins.q.get_state(state_ctx);
+        Circuit::draw(paint_ctx, self.kind, angle, q, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Self::describe_props(self.kind, props);
+        self.pins = Pins::new(self.kind, &description);
+        self.pins.all().cloned().collect()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        let clock = self
+            .pins
+            .clock
+            .as_ref()
+            .map(|pin| pin.get_state(state_ctx))
+            .unwrap_or_default();
+        let read_async = |pin: &Option<CircuitPinInfo>| match pin {
+            Some(pin) => read_bool(pin.get_state(state_ctx)),
+            None => Some(false),
+        };
+        let set = read_async(&self.pins.set);
+        let reset = read_async(&self.pins.reset);
+        let data: Vec<_> = self
+            .pins
+            .data
+            .iter()
+            .map(|pin| read_bool(pin.get_state(state_ctx)))
+            .collect();
+
+        let q = state_ctx.write_circuit_internal_state(|s: &mut State| {
+            let prev_clock = std::mem::replace(&mut s.clock, clock);
+            if s.q == WireState::None {
+                s.q = self.initial.into();
+            }
+
+            // Asynchronous inputs override everything else
+            s.q = match (set, reset) {
+                (None, _) | (_, None) | (Some(true), Some(true)) => WireState::Error,
+                (Some(true), _) => WireState::True,
+                (_, Some(true)) => WireState::False,
+                _ => self
+                    .next_value(&data, s.q, clock, prev_clock)
+                    .unwrap_or(s.q),
+            };
+            s.q
+        });
+
+        self.pins.q.set_state(state_ctx, q);
+        self.pins.nq.set_state(state_ctx, invert(q));
+    }
+
+    fn load_internal(
+        &self,
+        data: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn InternalCircuitState>> {
+        serde_intermediate::de::intermediate::deserialize::<State>(data)
+            .ok()
+            .map(|s| Box::new(s) as Box<dyn InternalCircuitState>)
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(self.kind, props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" => (true, true),
+            "async" => (false, true),
+            _ => (false, false),
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.edge = props.read_clone("edge").unwrap_or_default();
+        self.level = props.read_clone("level").unwrap_or_default();
+        self.initial = props.read_clone("initial").unwrap_or(false);
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {
+    pub kind: Kind,
+}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        self.kind.type_name().into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let angle = props
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        Circuit::draw(ctx, self.kind, angle, WireState::False, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new(self.kind))
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview { kind: self.kind }))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        let trigger = match self.kind {
+            Kind::SrLatch => None,
+            Kind::DLatch => Some(CircuitProperty::new("level", "Enable", ActiveLevel::High)),
+            _ => Some(CircuitProperty::new("edge", "Edge", Edge::Rising)),
+        };
+        CircuitPropertyStore::new(
+            [
+                CircuitProperty::new("dir", "Direction", Direction4::Right),
+                CircuitProperty::new("async", "Async set/reset", false),
+                CircuitProperty::new("initial", "Initial value", false),
+            ]
+            .into_iter()
+            .chain(trigger),
+        )
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        self.kind.display_name().into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(self.kind, props)
+    }
+}
+
+impl CircuitPropertyImpl for Edge {
+    fn equals(&self, other: &dyn CircuitPropertyImpl) -> bool {
+        other.is_type_and(|o: &Self| o == self)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, not_equal: bool) -> Option<Box<dyn CircuitPropertyImpl>> {
+        let old = *self;
+        let mut changed = false;
+        ui.skip_ahead_auto_ids(1);
+        ComboBox::from_id_source(ui.next_auto_id())
+            .selected_text(if not_equal { "" } else { self.name() })
+            .show_ui(ui, |ui| {
+                for edge in [Edge::Rising, Edge::Falling] {
+                    let res = ui.selectable_value(self, edge, edge.name());
+                    if res.changed() || res.clicked() {
+                        changed = true;
+                    }
+                }
+            });
+        changed.then(|| Box::new(old) as Box<dyn CircuitPropertyImpl>)
+    }
+
+    fn clone(&self) -> Box<dyn CircuitPropertyImpl> {
+        Box::new(*self)
+    }
+
+    fn load(&mut self, data: &serde_intermediate::Intermediate) {
+        if let Ok(d) = serde_intermediate::de::intermediate::deserialize(data) {
+            *self = d;
+        }
+    }
+
+    fn save(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap_or_default()
+    }
+
+    fn copy_into(&self, other: &mut dyn CircuitPropertyImpl) {
+        if let Some(r) = other.downcast_mut() {
+            *r = *self;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{
+        board::CircuitBoard,
+        state::{
+            test::{create_state, drive, driven_board, pin_state, settle},
+            State, WireState,
+        },
+        RwLock,
+    };
+
+    /// Asserts Q and !Q of flip-flop in circuit 0
+    fn assert_q(board: &Arc<RwLock<CircuitBoard>>, state: &State, q: WireState) {
+        let nq = match q {
+            WireState::True => WireState::False,
+            WireState::False => WireState::True,
+            q => q,
+        };
+        assert_eq!(pin_state(board, state, 0, "q"), q);
+        assert_eq!(pin_state(board, state, 0, "nq"), nq);
+    }
+
+    /// Drives clock from circuit `clock` through a full low-high-low cycle
+    fn pulse(board: &Arc<RwLock<CircuitBoard>>, state: &State, clock: usize) {
+        drive(board, state, clock, WireState::True);
+        drive(board, state, clock, WireState::False);
+    }
+
+    #[test]
+    fn d_flip_flop_edges() {
+        let board = driven_board("d_flip_flop", "", &["d", "clk"]);
+        let state = create_state(&board);
+        assert_q(&board, &state, WireState::False);
+
+        drive(&board, &state, 1, WireState::True);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 2, WireState::True);
+        assert_q(&board, &state, WireState::True);
+        drive(&board, &state, 1, WireState::False);
+        drive(&board, &state, 2, WireState::False);
+        assert_q(&board, &state, WireState::True);
+
+        let board = driven_board("d_flip_flop", r#""edge": "Falling""#, &["d", "clk"]);
+        let state = create_state(&board);
+        drive(&board, &state, 1, WireState::True);
+        drive(&board, &state, 2, WireState::True);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 2, WireState::False);
+        assert_q(&board, &state, WireState::True);
+    }
+
+    #[test]
+    fn flip_flop_async_priority() {
+        let props = r#""async": true"#;
+        let board = driven_board("d_flip_flop", props, &["d", "clk", "set", "reset"]);
+        let state = create_state(&board);
+
+        drive(&board, &state, 3, WireState::True);
+        assert_q(&board, &state, WireState::True);
+        // Clock edge can't override asynchronous set
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::True);
+
+        drive(&board, &state, 4, WireState::True);
+        assert_q(&board, &state, WireState::Error);
+        drive(&board, &state, 3, WireState::False);
+        assert_q(&board, &state, WireState::False);
+
+        drive(&board, &state, 1, WireState::True);
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 4, WireState::False);
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::True);
+    }
+
+    #[test]
+    fn sr_latch() {
+        let board = driven_board("sr_latch", "", &["s", "r"]);
+        let state = create_state(&board);
+
+        drive(&board, &state, 1, WireState::True);
+        assert_q(&board, &state, WireState::True);
+        drive(&board, &state, 1, WireState::False);
+        assert_q(&board, &state, WireState::True);
+        drive(&board, &state, 2, WireState::True);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 1, WireState::True);
+        assert_q(&board, &state, WireState::Error);
+    }
+
+    #[test]
+    fn jk_flip_flop_toggle() {
+        let board = driven_board("jk_flip_flop", "", &["j", "k", "clk"]);
+        let state = create_state(&board);
+
+        drive(&board, &state, 1, WireState::True);
+        drive(&board, &state, 2, WireState::True);
+        pulse(&board, &state, 3);
+        assert_q(&board, &state, WireState::True);
+        pulse(&board, &state, 3);
+        assert_q(&board, &state, WireState::False);
+
+        drive(&board, &state, 2, WireState::False);
+        pulse(&board, &state, 3);
+        assert_q(&board, &state, WireState::True);
+        pulse(&board, &state, 3);
+        assert_q(&board, &state, WireState::True);
+    }
+
+    #[test]
+    fn t_flip_flop_hold() {
+        let board = driven_board("t_flip_flop", "", &["t", "clk"]);
+        let state = create_state(&board);
+
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 1, WireState::True);
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::True);
+        drive(&board, &state, 1, WireState::False);
+        pulse(&board, &state, 2);
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::True);
+    }
+
+    #[test]
+    fn d_latch_enable() {
+        let board = driven_board("d_latch", "", &["d", "en"]);
+        let state = create_state(&board);
+
+        drive(&board, &state, 1, WireState::True);
+        assert_q(&board, &state, WireState::False);
+        drive(&board, &state, 2, WireState::True);
+        assert_q(&board, &state, WireState::True);
+        drive(&board, &state, 2, WireState::False);
+        drive(&board, &state, 1, WireState::False);
+        assert_q(&board, &state, WireState::True);
+
+        drive(&board, &state, 2, WireState::Error);
+        assert_q(&board, &state, WireState::Error);
+    }
+
+    #[test]
+    fn flip_flop_initial_value() {
+        let board = driven_board("d_flip_flop", r#""initial": true"#, &["d", "clk"]);
+        let state = create_state(&board);
+        assert_q(&board, &state, WireState::True);
+
+        pulse(&board, &state, 2);
+        assert_q(&board, &state, WireState::False);
+
+        state.reset();
+        state.init();
+        settle(&state);
+        assert_q(&board, &state, WireState::True);
+    }
+}
@@ -1,20 +1,13 @@
 use eframe::epaint::{Color32, FontId, Rounding, Stroke};
 use emath::{vec2, Align2, Rect};
 
-use crate::{circuits::*, containers::ConstRingBuffer, Direction4};
+use crate::{circuits::*, containers::ConstRingBuffer, state::to_duration, Direction4};
 
 use super::props::CircuitProperty;
 
+#[derive(Default)]
 struct State {
-    timings: ConstRingBuffer<64, Instant>,
-}
-
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            timings: ConstRingBuffer::new(),
-        }
-    }
+    timings: ConstRingBuffer<64, SimTime>,
 }
 
 impl InternalCircuitState for State {}
@@ -70,9 +63,9 @@ impl Circuit {
                     let count = s.timings.len();
                     let first = s.timings[0];
                     let last = s.timings[s.timings.len() - 1];
-                    match last.checked_duration_since(first) {
-                        None => 0.0,
-                        Some(d) => 1.0 / d.as_secs_f32() * count as f32,
+                    match last.checked_sub(first) {
+                        None | Some(0) => 0.0,
+                        Some(t) => 1.0 / to_duration(t).as_secs_f32() * count as f32,
                     }
                 })
                 .unwrap_or(0.0),
@@ -108,6 +101,7 @@ impl Circuit {
                     Direction4::Down => [2, 2],
                     Direction4::Right => [4, 1],
                 }.into(),
+                width: 1,
             }],
         }
     }
@@ -126,8 +120,8 @@ impl CircuitImpl for Circuit {
 
     fn update_signals(&self, state_ctx: &CircuitStateContext, changed_pin: Option<usize>) {
         if changed_pin == Some(0) && self.input.get_state(state_ctx) == WireState::True {
-            state_ctx
-                .write_circuit_internal_state(|s: &mut State| s.timings.push_back(Instant::now()))
+            let time = state_ctx.global_state.sim_time();
+            state_ctx.write_circuit_internal_state(|s: &mut State| s.timings.push_back(time))
         }
     }
 
@@ -1,12 +1,14 @@
-use eframe::epaint::{Color32, Stroke};
-use emath::Vec2;
+use eframe::{
+    egui::Ui,
+    epaint::{Color32, Stroke},
+};
+use emath::{vec2, Pos2, Rect, Vec2};
 
 use crate::{
     circuits::{
-        props::{CircuitProperty, CircuitPropertyStore},
+        props::{CircuitProperty, CircuitPropertyImpl, CircuitPropertyStore},
         *,
     },
-    describe_directional_circuit,
     path::{PathItem, PathItemIterator},
     vector::Vec2f,
     Direction4, Mutex,
@@ -24,88 +26,228 @@ thread_local! {
     static INPUT_BOOLS: Mutex<Vec<bool>> = Default::default();
 }
 
+const MIN_INPUTS: u32 = 2;
+const MAX_INPUTS: u32 = 32;
+
+/// Reads "inputs" property, clamped to supported input counts
+fn read_inputs_prop(props: &CircuitPropertyStore) -> u32 {
+    props
+        .read_clone::<u32>("inputs")
+        .unwrap_or(MIN_INPUTS)
+        .clamp(MIN_INPUTS, MAX_INPUTS)
+}
+
+/// Body height, odd so output stays centered
+fn gate_height(inputs: u32) -> u32 {
+    inputs | 1
+}
+
+/// Input rows, middle row is skipped if there are less inputs than rows
+fn input_row(index: u32, inputs: u32) -> u32 {
+    let height = gate_height(inputs);
+    if height != inputs && index >= height / 2 {
+        index + 1
+    } else {
+        index
+    }
+}
+
+fn input_display_name(index: u32) -> DynStaticStr {
+    match index {
+        0..=25 => char::from(b'A' + index as u8).to_string().into(),
+        _ => index.to_string().into(),
+    }
+}
+
+/// Per-input inversion flags, one for every gate input
+#[derive(Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Negated(Vec<bool>);
+
+impl Negated {
+    fn get(&self, index: usize) -> bool {
+        self.0.get(index).copied().unwrap_or(false)
+    }
+}
+
+/// Resizes "negated" property to new input count, so its ui shows every input.
+/// Called only when input count changes, shorter lists from older boards still work
+fn inputs_changed(props: &CircuitPropertyStore) {
+    let inputs = read_inputs_prop(props) as usize;
+    props.write("negated", |n: &mut Negated| n.0.resize(inputs, false));
+}
+
 struct Circuit {
     template: GateTemplate,
     inputs: Box<[CircuitPinInfo]>,
     dir: Direction4,
     output: CircuitPinInfo,
+    negated: Negated,
+    negate_output: bool,
 }
 
 impl Circuit {
     fn new(template: GateTemplate) -> Self {
-        let description = Self::describe(Direction4::Right);
+        let description = Self::describe(Direction4::Right, MIN_INPUTS, 1);
         Self {
             template,
-            inputs: vec![
-                description.pins[0].to_info(),
-                description.pins[1].to_info(),
-            ]
-            .into_boxed_slice(),
-            output: description.pins[2].to_info(),
+            inputs: description.pins[..MIN_INPUTS as usize]
+                .iter()
+                .map(|p| p.to_info())
+                .collect(),
+            output: description.pins[MIN_INPUTS as usize].to_info(),
             dir: Direction4::Right,
+            negated: Negated::default(),
+            negate_output: false,
         }
     }
 
-    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<3> {
+    fn draw(
+        ctx: &PaintContext,
+        template: &GateTemplate,
+        dir: Direction4,
+        inputs: u32,
+        negated: &Negated,
+        negate_output: bool,
+        semi_transparent: bool,
+    ) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = Color32::from_gray(200).linear_multiply(opacity);
+        let stroke = Stroke::new(0.15 * ctx.screen.scale, border_color);
+
+        let angle = dir.inverted_ud().angle_to_right();
+        let height = gate_height(inputs) as f32;
+        let size = vec2(4.0, height);
+        let transformer = |x: f32, y: f32| -> Pos2 {
+            ctx.rect
+                .lerp_inside(Vec2f::from(vec2(x, y) / size).rotated_xy(angle, 0.5).into())
+        };
+
+        // Templates draw 4x3 bodies, taller gates get the body centered
+        // and their back extended to reach every input
+        let body_top = (height - 3.0) / 2.0;
+        if body_top > 0.0 {
+            ctx.paint
+                .line_segment([transformer(0.5, 0.5), transformer(0.5, body_top)], stroke);
+            ctx.paint.line_segment(
+                [
+                    transformer(0.5, height - body_top),
+                    transformer(0.5, height - 0.5),
+                ],
+                stroke,
+            );
+        }
+        let body_size = match dir.is_horizontal() {
+            true => vec2(4.0, 3.0),
+            false => vec2(3.0, 4.0),
+        };
+        let body_rect = Rect::from_center_size(ctx.rect.center(), body_size * ctx.screen.scale);
+        (template.drawer)(&ctx.with_rect(body_rect), angle, semi_transparent);
+
+        let bubble = |x: f32, y: f32| {
+            ctx.paint.circle(
+                transformer(x, y),
+                0.2 * ctx.screen.scale,
+                fill_color,
+                stroke,
+            );
+        };
+        for i in 0..inputs {
+            if negated.get(i as usize) {
+                bubble(0.3, input_row(i, inputs) as f32 + 0.5);
+            }
+        }
+        if negate_output {
+            bubble(3.7, height / 2.0);
+        }
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> DynCircuitDescription {
         let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
-        Self::describe(dir)
+        Self::describe(dir, read_inputs_prop(props), read_width_prop(props))
     }
 
-    fn describe(dir: Direction4) -> CircuitDescription<3> {
-        describe_directional_circuit! {
-            default_dir: Right,
-            dir: dir,
-            size: [4, 3],
-            "in_0": Inside, "A", Left, [0, 0],
-            "in_1": Inside, "B", Left, [0, 2],
-            "out": Outside, "Out", Right, [3, 1],
-        }
+    fn describe(dir: Direction4, inputs: u32, width: u32) -> DynCircuitDescription {
+        let height = gate_height(inputs);
+        let inputs = (0..inputs).map(|i| CircuitPinDescription {
+            display_name: input_display_name(i),
+            display_dir: Some(Direction4::Left),
+            dir: InternalPinDirection::Inside,
+            name: format!("in_{i}").into(),
+            pos: [0, input_row(i, inputs)].into(),
+            width,
+        });
+        let output = CircuitPinDescription {
+            display_name: "Out".into(),
+            display_dir: Some(Direction4::Right),
+            dir: InternalPinDirection::Outside,
+            name: "out".into(),
+            pos: [3, height / 2].into(),
+            width,
+        };
+
+        describe_directional_dyn(
+            Direction4::Right,
+            dir,
+            [4, height],
+            inputs.chain(std::iter::once(output)),
+        )
     }
 }
 
 impl CircuitImpl for Circuit {
     fn draw(&self, _: &CircuitStateContext, paint_ctx: &PaintContext) {
-        let angle = self.dir.inverted_ud().angle_to_right();
-        (self.template.drawer)(paint_ctx, angle, false);
+        Circuit::draw(
+            paint_ctx,
+            &self.template,
+            self.dir,
+            self.inputs.len() as u32,
+            &self.negated,
+            self.negate_output,
+            false,
+        );
     }
 
     fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
         let description = Self::describe_props(props);
-        self.inputs = vec![
-            description.pins[0].to_info(),
-            description.pins[1].to_info(),
-        ]
-        .into_boxed_slice();
-        self.output = description.pins[2].to_info();
+        let (inputs, output) = description.pins.split_at(description.pins.len() - 1);
+        self.inputs = inputs.iter().map(|p| p.to_info()).collect();
+        self.output = output[0].to_info();
         let mut vec = vec![self.output.clone()];
         vec.extend(self.inputs.iter().cloned());
         vec.into_boxed_slice()
     }
 
     fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
-        let states = self.inputs.iter().map(|i| i.get_state(state_ctx));
-        INPUT_BOOLS.with(|b| {
+        let width = self.output.width;
+        let output = INPUT_BOOLS.with(|b| {
             let mut b = b.lock();
 
-            b.clear();
-            for state in states {
-                match state {
-                    WireState::None => continue,
-                    WireState::True => b.push(true),
-                    WireState::False => b.push(false),
-                    WireState::Error => {
-                        self.output.set_state(state_ctx, WireState::Error);
-                        return;
+            let bits = (0..width).map(|bit| {
+                b.clear();
+                for (i, input) in self.inputs.iter().enumerate() {
+                    let negated = self.negated.get(i);
+                    match input.get_state(state_ctx).bit(bit) {
+                        WireState::None => continue,
+                        WireState::True => b.push(!negated),
+                        WireState::False => b.push(negated),
+                        WireState::Error | WireState::Bus(_) => return WireState::Error,
                     }
                 }
-            }
-            if b.is_empty() {
-                self.output.set_state(state_ctx, WireState::None);
-            } else {
-                self.output
-                    .set_state(state_ctx, (self.template.process_inputs)(&b).into());
-            }
+                if b.is_empty() {
+                    WireState::None
+                } else {
+                    ((self.template.process_inputs)(&b) != self.negate_output).into()
+                }
+            });
+            WireState::from_bits(width, bits)
         });
+        self.output.set_state(state_ctx, output);
+    }
+
+    fn is_combinational(&self) -> bool {
+        true
     }
 
     fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
@@ -113,14 +255,20 @@ impl CircuitImpl for Circuit {
     }
 
     fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
-        if prop_id == "dir" {
-            *resize = true;
-            *recreate_pins = true;
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" | "inputs" => (true, true),
+            "width" => (false, true),
+            _ => (false, false),
         }
     }
 
-    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+    fn apply_props(&mut self, props: &CircuitPropertyStore, changed: Option<&str>) {
+        if changed == Some("inputs") {
+            inputs_changed(props);
+        }
         self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        self.negated = props.read_clone("negated").unwrap_or_default();
+        self.negate_output = props.read_clone("negate_output").unwrap_or(false);
     }
 }
 
@@ -131,10 +279,17 @@ pub struct Preview {
 impl CircuitPreviewImpl for Preview {
     fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
         let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
-        let angle = dir
-            .inverted_ud()
-            .angle_to_right();
-        (self.template.drawer)(ctx, angle, in_world);
+        let negated = props.read_clone("negated").unwrap_or_default();
+        let negate_output = props.read_clone("negate_output").unwrap_or(false);
+        Circuit::draw(
+            ctx,
+            &self.template,
+            dir,
+            read_inputs_prop(props),
+            &negated,
+            negate_output,
+            in_world,
+        );
     }
 
     fn create_impl(&self) -> Box<dyn CircuitImpl> {
@@ -155,7 +310,13 @@ impl CircuitPreviewImpl for Preview {
     }
 
     fn default_props(&self) -> CircuitPropertyStore {
-        CircuitPropertyStore::new([CircuitProperty::new("dir", "Direction", Direction4::Right)])
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("width", "Width", 1u32),
+            CircuitProperty::new("inputs", "Inputs", MIN_INPUTS),
+            CircuitProperty::new("negated", "Negated inputs", Negated(vec![false; 2])),
+            CircuitProperty::new("negate_output", "Negate output", false),
+        ])
     }
 
     fn display_name(&self) -> DynStaticStr {
@@ -163,8 +324,55 @@ impl CircuitPreviewImpl for Preview {
     }
 
     fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
-        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
-        Circuit::describe(dir).to_dyn()
+        Circuit::describe_props(props)
+    }
+
+    fn prop_changed(&self, props: &CircuitPropertyStore, prop_id: &str) {
+        if prop_id == "inputs" {
+            inputs_changed(props);
+        }
+    }
+}
+
+impl CircuitPropertyImpl for Negated {
+    fn equals(&self, other: &dyn CircuitPropertyImpl) -> bool {
+        other.is_type_and(|o: &Self| o == self)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, not_equal: bool) -> Option<Box<dyn CircuitPropertyImpl>> {
+        let old = Clone::clone(self);
+        let mut changed = false;
+        ui.horizontal_wrapped(|ui| {
+            for (i, negated) in self.0.iter_mut().enumerate() {
+                let mut selected = !not_equal && *negated;
+                let name = input_display_name(i as u32);
+                if ui.toggle_value(&mut selected, &*name).changed() {
+                    *negated = selected;
+                    changed = true;
+                }
+            }
+        });
+        changed.then(|| Box::new(old) as Box<dyn CircuitPropertyImpl>)
+    }
+
+    fn clone(&self) -> Box<dyn CircuitPropertyImpl> {
+        Box::new(Clone::clone(self))
+    }
+
+    fn load(&mut self, data: &serde_intermediate::Intermediate) {
+        if let Ok(d) = serde_intermediate::de::intermediate::deserialize(data) {
+            *self = d;
+        }
+    }
+
+    fn save(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap_or_default()
+    }
+
+    fn copy_into(&self, other: &mut dyn CircuitPropertyImpl) {
+        if let Some(r) = other.downcast_mut::<Self>() {
+            r.clone_from(self);
+        }
     }
 }
 
@@ -197,3 +405,58 @@ pub fn draw_from_path(
         },
     );
 }
+
+#[cfg(test)]
+mod test {
+    use serde_intermediate::Intermediate;
+
+    use super::Preview;
+    use crate::{
+        circuits::{gates::and, CircuitPreview},
+        state::{
+            test::{create_state, drive, driven_board, pin_state},
+            WireState,
+        },
+    };
+
+    #[test]
+    fn gate_inputs() {
+        let inputs = ["in_0", "in_1", "in_2", "in_3"];
+        let board = driven_board("and", r#""inputs": 4"#, &inputs);
+        let state = create_state(&board);
+        for button in 1..=3 {
+            drive(&board, &state, button, WireState::True);
+        }
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::False);
+        drive(&board, &state, 4, WireState::True);
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::True);
+
+        let props = r#""inputs": 3, "negated": [false, true, false], "negate_output": true"#;
+        let board = driven_board("or", props, &inputs[..3]);
+        let state = create_state(&board);
+        // Negated input is high while its button is released
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::False);
+        drive(&board, &state, 2, WireState::True);
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::True);
+        drive(&board, &state, 3, WireState::True);
+        assert_eq!(pin_state(&board, &state, 0, "out"), WireState::False);
+    }
+
+    #[test]
+    fn gate_negated_resize() {
+        let preview = CircuitPreview::from_impl(Box::new(Preview {
+            template: and::TEMPLATE,
+        }));
+        let negated = |preview: &CircuitPreview| match &preview.props.save().0["negated"] {
+            Intermediate::Seq(negated) => negated.len(),
+            other => panic!("unexpected negated value {other:?}"),
+        };
+
+        preview.props.write("inputs", |inputs: &mut u32| *inputs = 5);
+        preview.prop_changed("dir");
+        assert_eq!(negated(&preview), 2);
+        preview.prop_changed("inputs");
+        assert_eq!(negated(&preview), 5);
+        assert_eq!(preview.describe().pins.len(), 6);
+    }
+}
@@ -4,7 +4,7 @@ use emath::{vec2, Pos2, pos2};
 use crate::{
     circuits::{
         CircuitImpl, CircuitPinInfo, CircuitPreviewImpl, CircuitPropertyStore, CircuitStateContext,
-        InternalPinDirection, props::CircuitProperty, CircuitDescription,
+        InternalPinDirection, props::CircuitProperty, CircuitDescription, read_width_prop,
     },
     state::WireState,
     vector::{Vec2u, Vec2f},
@@ -63,7 +63,7 @@ impl Circuit {
 
     fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<2> {
         let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
-        Self::describe(dir)
+        Self::describe(dir).with_width(read_width_prop(props))
     }
 
     fn describe(dir: Direction4) -> CircuitDescription<2> {
@@ -93,23 +93,28 @@ impl CircuitImpl for Circuit {
 
     fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
         let state = self.input.get_state(state_ctx);
-        let state = match state {
+        let state = state.map_bits(self.output.width, |bit| match bit {
             WireState::None => WireState::None,
             WireState::True => WireState::False,
             WireState::False => WireState::True,
-            WireState::Error => WireState::Error,
-        };
+            WireState::Error | WireState::Bus(_) => WireState::Error,
+        });
         self.output.set_state(state_ctx, state);
     }
 
+    fn is_combinational(&self) -> bool {
+        true
+    }
+
     fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
         Self::describe_props(props).size
     }
 
     fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
-        if prop_id == "dir" {
-            *resize = true;
-            *recreate_pins = true;
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" => (true, true),
+            "width" => (false, true),
+            _ => (false, false),
         }
     }
 
@@ -147,7 +152,8 @@ impl CircuitPreviewImpl for Preview {
 
     fn default_props(&self) -> CircuitPropertyStore {
         CircuitPropertyStore::new([
-            CircuitProperty::new("dir", "Direction", Direction4::Right)
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("width", "Width", 1u32),
         ])
     }
 
@@ -0,0 +1,150 @@
+use crate::{
+    circuits::{
+        props::{ActiveLevel, CircuitProperty},
+        seven_segment::draw_display,
+        *,
+    },
+    describe_directional_circuit, Direction4,
+};
+
+/// Segments a to g of every hex digit, segment a in the lowest bit
+const DIGITS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+
+/// Segments showing `value`, decimal point is never lit
+fn digit_segments(value: u8) -> [Option<bool>; 8] {
+    let mask = DIGITS[value as usize & 0xf];
+    std::array::from_fn(|i| Some(i < 7 && mask & (1 << i) != 0))
+}
+
+struct Circuit {
+    inputs: [CircuitPinInfo; 4],
+    dir: Direction4,
+    level: ActiveLevel,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            inputs: description.pins.each_ref().map(|pin| pin.to_info()),
+            dir: Direction4::Right,
+            level: ActiveLevel::High,
+        }
+    }
+
+    fn draw(
+        ctx: &PaintContext,
+        dir: Direction4,
+        segments: [Option<bool>; 8],
+        semi_transparent: bool,
+    ) {
+        let size = Self::describe(Direction4::Right).size;
+        draw_display(ctx, size, dir, segments, semi_transparent);
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<4> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<4> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [4, 6],
+
+            "in_0": Inside, "0", Down, [3, 5],
+            "in_1": Inside, "1", Down, [2, 5],
+            "in_2": Inside, "2", Down, [1, 5],
+            "in_3": Inside, "3", Down, [0, 5]
+        }
+    }
+
+    /// Blank while any input is floating, all segments show an error for invalid inputs
+    fn segments(&self, state_ctx: &CircuitStateContext) -> [Option<bool>; 8] {
+        let mut value = 0;
+        for (i, pin) in self.inputs.iter().enumerate() {
+            let state = pin.get_state(state_ctx);
+            if state == WireState::None {
+                return [Some(false); 8];
+            }
+            match self.level.is_active(state) {
+                Some(bit) => value |= (bit as u8) << i,
+                None => return [None; 8],
+            }
+        }
+        digit_segments(value)
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        Circuit::draw(paint_ctx, self.dir, self.segments(state_ctx), false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.inputs = description.pins.each_ref().map(|pin| pin.to_info());
+        self.inputs.to_vec().into_boxed_slice()
+    }
+
+    fn update_signals(&self, _: &CircuitStateContext, _: Option<usize>) {}
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *resize = true;
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        self.level = props.read_clone("level").unwrap_or_default();
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "hex_display".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Circuit::draw(ctx, dir, digit_segments(0xf), in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("level", "Level", ActiveLevel::High),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "Hex display".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
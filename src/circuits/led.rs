@@ -0,0 +1,177 @@
+use eframe::epaint::{Color32, Stroke};
+use emath::Pos2;
+
+use crate::{
+    circuits::{
+        props::{ActiveLevel, CircuitProperty},
+        *,
+    },
+    describe_directional_circuit,
+    vector::Vec2f,
+    Direction4,
+};
+
+const DEFAULT_COLOR: Color32 = Color32::from_rgb(255, 40, 40);
+
+/// Draws LED body with a glow around it while lit. `lit` is None for invalid input
+pub(super) fn draw_led(
+    ctx: &PaintContext,
+    center: Pos2,
+    radius: f32,
+    color: Color32,
+    lit: Option<bool>,
+    opacity: f32,
+) {
+    let [r, g, b, _] = color.to_array();
+    let dark = Color32::from_rgb(r / 4, g / 4, b / 4).linear_multiply(opacity);
+    let border_color = match lit {
+        None => WireState::Error.color(),
+        Some(_) => Color32::BLACK,
+    }
+    .linear_multiply(opacity);
+
+    if lit == Some(true) {
+        for i in (1..=4).rev() {
+            let glow = color.linear_multiply(opacity * 0.3 / i as f32);
+            ctx.paint
+                .circle_filled(center, radius * (1.0 + 0.2 * i as f32), glow);
+        }
+    }
+    let fill = match lit {
+        Some(true) => color.linear_multiply(opacity),
+        _ => dark,
+    };
+    ctx.paint.circle(
+        center,
+        radius,
+        fill,
+        Stroke::new(0.1 * ctx.screen.scale, border_color),
+    );
+}
+
+struct Circuit {
+    input: CircuitPinInfo,
+    dir: Direction4,
+    color: Color32,
+    level: ActiveLevel,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            input: description.pins[0].to_info(),
+            dir: Direction4::Right,
+            color: DEFAULT_COLOR,
+            level: ActiveLevel::High,
+        }
+    }
+
+    fn draw(
+        ctx: &PaintContext,
+        angle: f32,
+        color: Color32,
+        lit: Option<bool>,
+        semi_transparent: bool,
+    ) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+        let center = ctx
+            .rect
+            .lerp_inside(Vec2f::from([0.75, 0.5]).rotated_xy(angle, 0.5).into());
+        draw_led(ctx, center, ctx.screen.scale * 0.4, color, lit, opacity);
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<1> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<1> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [2, 1],
+
+            "in": Inside, "In", Left, [0, 0]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let angle = self.dir.inverted_ud().angle_to_right();
+        let lit = self.level.is_active(self.input.get_state(state_ctx));
+        Circuit::draw(paint_ctx, angle, self.color, lit, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.input = description.pins[0].to_info();
+        vec![self.input.clone()].into_boxed_slice()
+    }
+
+    fn update_signals(&self, _: &CircuitStateContext, _: Option<usize>) {}
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *resize = true;
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        self.color = props.read_clone("color").unwrap_or(DEFAULT_COLOR);
+        self.level = props.read_clone("level").unwrap_or_default();
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "led".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let angle = props
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        let color = props.read_clone("color").unwrap_or(DEFAULT_COLOR);
+        Circuit::draw(ctx, angle, color, Some(true), in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("color", "Color", DEFAULT_COLOR),
+            CircuitProperty::new("level", "Level", ActiveLevel::High),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "LED".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
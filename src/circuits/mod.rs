@@ -1,29 +1,120 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
+use eframe::egui::{Response, Sense};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     board::ActiveCircuitBoard,
-    state::{CircuitState, InternalCircuitState, State, StateCollection, WireState},
-    time::Instant,
+    state::{CircuitState, InternalCircuitState, SimTime, State, StateCollection, WireState},
     vector::{Vec2i, Vec2u, Vector},
     Direction4, DynStaticStr, OptionalInt, PaintContext, RwLock,
 };
 
 use self::props::CircuitPropertyStore;
 
+pub mod bus;
 pub mod button;
+pub mod clock;
+pub mod constant;
+pub mod flip_flop;
 pub mod freq_meter;
 pub mod gates;
+pub mod hex_display;
+pub mod led;
+pub mod plexers;
 pub mod props;
-pub mod pullup;
+pub mod pull;
+pub mod rgb_led;
+pub mod seven_segment;
+pub mod switch;
 pub mod transistor;
+pub mod tristate;
 
 // so template is always valid
 #[cfg(test)]
 #[path = "../../templates/circuit_template.rs"]
 mod circuit_template;
 
+/// Previews of every built-in circuit type
+pub fn builtin_previews() -> Vec<Box<dyn CircuitPreviewImpl>> {
+    vec![
+        Box::new(button::Preview {}),
+        Box::new(switch::Preview {}),
+        Box::new(constant::Preview { value: true }),
+        Box::new(constant::Preview { value: false }),
+        Box::new(gates::gate::Preview {
+            template: gates::or::TEMPLATE,
+        }),
+        Box::new(gates::gate::Preview {
+            template: gates::nor::TEMPLATE,
+        }),
+        Box::new(gates::gate::Preview {
+            template: gates::xor::TEMPLATE,
+        }),
+        Box::new(gates::gate::Preview {
+            template: gates::xnor::TEMPLATE,
+        }),
+        Box::new(gates::gate::Preview {
+            template: gates::and::TEMPLATE,
+        }),
+        Box::new(gates::gate::Preview {
+            template: gates::nand::TEMPLATE,
+        }),
+        Box::new(gates::not::Preview {}),
+        Box::new(pull::Preview { value: true }),
+        Box::new(pull::Preview { value: false }),
+        Box::new(transistor::Preview {}),
+        Box::new(tristate::Preview {}),
+        Box::new(freq_meter::Preview {}),
+        Box::new(clock::Preview {}),
+        Box::new(flip_flop::Preview {
+            kind: flip_flop::Kind::SrLatch,
+        }),
+        Box::new(flip_flop::Preview {
+            kind: flip_flop::Kind::DLatch,
+        }),
+        Box::new(flip_flop::Preview {
+            kind: flip_flop::Kind::DFlipFlop,
+        }),
+        Box::new(flip_flop::Preview {
+            kind: flip_flop::Kind::JkFlipFlop,
+        }),
+        Box::new(flip_flop::Preview {
+            kind: flip_flop::Kind::TFlipFlop,
+        }),
+        Box::new(led::Preview {}),
+        Box::new(rgb_led::Preview {}),
+        Box::new(seven_segment::Preview {}),
+        Box::new(hex_display::Preview {}),
+        Box::new(plexers::Preview {
+            kind: plexers::Kind::Multiplexer,
+        }),
+        Box::new(plexers::Preview {
+            kind: plexers::Kind::Demultiplexer,
+        }),
+        Box::new(plexers::Preview {
+            kind: plexers::Kind::Decoder,
+        }),
+        Box::new(plexers::Preview {
+            kind: plexers::Kind::PriorityEncoder,
+        }),
+        Box::new(bus::Preview {}),
+    ]
+}
+
+/// Previews of every built-in circuit type by type name, with default data
+// Previews are only used from the UI thread, so they don't need to be Send or Sync
+#[allow(clippy::arc_with_non_send_sync)]
+pub fn builtin_preview_map() -> HashMap<DynStaticStr, Arc<CircuitPreview>> {
+    builtin_previews()
+        .into_iter()
+        .map(|p| {
+            let p = CircuitPreview::from_impl(p);
+            (p.imp.type_name(), Arc::new(p))
+        })
+        .collect()
+}
+
 pub struct CircuitInfo {
     pub size: Vec2u,
     pub pins: Box<[CircuitPinInfo]>,
@@ -34,6 +125,7 @@ pub enum InternalPinDirection {
     StateDependent { default: PinDirection },
     Inside,
     Outside,
+    Weak,
     Custom,
 }
 
@@ -43,6 +135,9 @@ pub enum PinDirection {
 
     #[default]
     Outside,
+    /// Drives only the bits that no [`PinDirection::Outside`] pin on the wire drives,
+    /// like a pull resistor. Disagreeing weak drivers give an error
+    Weak,
     Custom,
 }
 
@@ -64,6 +159,7 @@ pub struct CircuitPin {
     pub id: CircuitPinId,
     pub(crate) wire: Option<usize>,
     pub(crate) dir: InternalPinDirection,
+    pub(crate) width: u32,
 }
 
 impl CircuitPin {
@@ -71,37 +167,25 @@ impl CircuitPin {
         match self.dir {
             InternalPinDirection::Inside => PinDirection::Inside,
             InternalPinDirection::Outside => PinDirection::Outside,
+            InternalPinDirection::Weak => PinDirection::Weak,
             InternalPinDirection::Custom => PinDirection::Custom,
             InternalPinDirection::StateDependent { default } => state
-                .read_circuit(self.id.circuit_id)
-                .map(|cs| {
-                    cs.read()
-                        .pin_dirs
-                        .get(self.id.id)
-                        .cloned()
-                        .unwrap_or(default)
-                })
+                .pins
+                .dir(self.id.circuit_id, self.id.id)
                 .unwrap_or(default),
         }
     }
 
     pub fn get_state(&self, state: &State) -> WireState {
-        state
-            .read_circuit(self.id.circuit_id)
-            .map(|cs| cs.read().pins.get(self.id.id).copied().unwrap_or_default())
-            .unwrap_or_default()
+        state.pins.get(self.id.circuit_id, self.id.id)
     }
 
     pub fn set_input(&self, state: &State, value: WireState, update_state: bool) {
-        let circuit = state.get_circuit(self.id.circuit_id);
-        let mut circuit = circuit.write();
-
-        let current = circuit.pins.get_clone(self.id.id).unwrap_or_default();
+        let current = state.pins.swap(self.id.circuit_id, self.id.id, value);
         if current == value {
             return;
         }
 
-        circuit.pins.set(value, self.id.id);
         if update_state {
             match self.dir {
                 InternalPinDirection::Custom => {
@@ -121,6 +205,11 @@ impl CircuitPin {
         self.wire
     }
 
+    /// Bit width of values this pin carries
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
     pub fn set_wire(
         &mut self,
         states: &StateCollection,
@@ -149,7 +238,7 @@ impl CircuitPin {
                 InternalPinDirection::StateDependent { default: _ } => {
                     states.update_pin_input(self.id.circuit_id, self.id.id);
                 }
-                InternalPinDirection::Outside => {}
+                InternalPinDirection::Outside | InternalPinDirection::Weak => {}
                 InternalPinDirection::Inside => {
                     states.update_pin_input(self.id.circuit_id, self.id.id);
                 }
@@ -167,20 +256,13 @@ pub struct CircuitPinInfo {
     pub display_dir: Option<Direction4>,
     pub name: DynStaticStr,
     pub pos: Vec2u,
+    pub width: u32,
     pub pin: Arc<RwLock<CircuitPin>>,
 }
 
 impl CircuitPinInfo {
     pub fn get_state(&self, state_ctx: &CircuitStateContext) -> WireState {
-        state_ctx
-            .read_circuit_state()
-            .map(|cs| {
-                cs.read()
-                    .pins
-                    .get_clone(self.pin.read().id.id)
-                    .unwrap_or_default()
-            })
-            .unwrap_or_default()
+        self.pin.read().get_state(state_ctx.global_state)
     }
 
     pub fn get_wire_state(&self, state_ctx: &CircuitStateContext) -> Option<WireState> {
@@ -190,22 +272,65 @@ impl CircuitPinInfo {
             .map(|wire| state_ctx.global_state.read_wire(wire))
     }
 
+    /// Changes output state after circuit's propagation delay
     pub fn set_state(&self, state_ctx: &CircuitStateContext, value: WireState) {
+        let delay = state_ctx.output_delay(self.pin.read().wire);
+        if delay > 0 {
+            self.schedule_output(state_ctx, value, None, delay);
+            return;
+        }
+        self.set_state_now(state_ctx, value);
+    }
+
+    /// Changes output state and pin direction together after circuit's propagation delay,
+    /// so pin doesn't drive its previous state between the two
+    pub fn set_state_and_direction(
+        &self,
+        state_ctx: &CircuitStateContext,
+        value: WireState,
+        dir: PinDirection,
+    ) {
+        let delay = state_ctx.delay();
+        if delay > 0 {
+            self.schedule_output(state_ctx, value, Some(dir), delay);
+            return;
+        }
+        self.set_state_now(state_ctx, value);
+        self.set_direction(state_ctx, dir);
+    }
+
+    fn schedule_output(
+        &self,
+        state_ctx: &CircuitStateContext,
+        value: WireState,
+        dir: Option<PinDirection>,
+        delay: SimTime,
+    ) {
+        let pin_id = self.pin.read().id;
+        let pins = &state_ctx.global_state.pins;
+        let current = (
+            pins.get(pin_id.circuit_id, pin_id.id),
+            pins.dir(pin_id.circuit_id, pin_id.id),
+        );
+        state_ctx
+            .global_state
+            .schedule_pin_output(pin_id, current, value, dir, delay);
+    }
+
+    fn set_state_now(&self, state_ctx: &CircuitStateContext, value: WireState) {
         let pin = self.pin.read();
 
         let current = state_ctx
-            .read_circuit_state()
-            .map(|arc| arc.read().pins.get_clone(pin.id.id).unwrap_or_default())
-            .unwrap_or_default();
+            .global_state
+            .pins
+            .swap(pin.id.circuit_id, pin.id.id, value);
         if current == value {
             return;
         }
 
         state_ctx
-            .get_circuit_state()
-            .write()
-            .pins
-            .set(value, pin.id.id);
+            .global_state
+            .pin_changed(pin.id.circuit_id, &pin.name, current, value);
         if let Some(wire) = pin.wire {
             state_ctx.global_state.update_wire(wire, false)
         }
@@ -216,15 +341,12 @@ impl CircuitPinInfo {
             let pin = self.pin.read();
             (pin.id, pin.wire)
         };
-        {
-            let state = state_ctx.get_circuit_state();
-            let mut state = state.write();
-
-            if state.pin_dirs.get(pin_id.id).is_some_and(|d| *d == dir) {
-                return;
-            }
-
-            state.pin_dirs.set(dir, pin_id.id);
+        let current = state_ctx
+            .global_state
+            .pins
+            .swap_dir(pin_id.circuit_id, pin_id.id, dir);
+        if current == Some(dir) {
+            return;
         }
 
         match dir {
@@ -238,6 +360,14 @@ impl CircuitPinInfo {
             PinDirection::Outside => state_ctx
                 .global_state
                 .update_circuit_signals(pin_id.circuit_id, Some(pin_id.id)),
+            PinDirection::Weak => {
+                if let Some(wire) = wire {
+                    state_ctx.global_state.update_wire(wire, true);
+                }
+                state_ctx
+                    .global_state
+                    .update_circuit_signals(pin_id.circuit_id, Some(pin_id.id))
+            }
             PinDirection::Custom => match wire {
                 Some(wire) => state_ctx.global_state.update_wire(wire, true),
                 None => self
@@ -256,15 +386,18 @@ impl CircuitPinInfo {
         name: impl Into<DynStaticStr>,
         display_name: impl Into<DynStaticStr>,
         display_dir: impl Into<Option<Direction4>>,
+        width: u32,
     ) -> Self {
         let name = name.into();
         Self {
             pos: pos.into(),
+            width,
             pin: Arc::new(RwLock::new(CircuitPin {
                 id: Default::default(),
                 dir,
                 wire: None,
                 name: name.clone(),
+                width,
             })),
             name,
             display_name: display_name.into(),
@@ -330,6 +463,11 @@ impl Circuit {
         }
     }
 
+    /// Propagation delay of circuit outputs
+    pub fn delay(&self) -> SimTime {
+        self.props.read_clone::<u32>("delay").unwrap_or(1) as SimTime
+    }
+
     pub fn copy(&self, pos: Vec2u, state: &State) -> crate::io::CircuitCopyData {
         let internal = state
             .read_circuit(self.id)
@@ -348,14 +486,16 @@ impl Circuit {
             pos,
             imp: self.imp.read().save(),
             internal,
-            update: state
-                .updates
-                .lock()
-                .iter()
-                .find_map(|(id, time)| {
-                    (*id == self.id).then(|| time.checked_duration_since(Instant::now()))
-                })
-                .flatten(),
+            update: {
+                let now = state.sim_time();
+                state
+                    .updates
+                    .lock()
+                    .iter()
+                    .find_map(|(id, time)| (*id == self.id).then(|| time.checked_sub(now)))
+                    .flatten()
+                    .map(crate::state::to_duration)
+            },
             props: self.props.save(),
         }
     }
@@ -414,6 +554,29 @@ impl<'a> CircuitStateContext<'a> {
     pub fn props(&self) -> &CircuitPropertyStore {
         &self.circuit.props
     }
+
+    /// Pointer interaction with the middle of the circuit, for circuits the user operates
+    pub fn interact(&self, paint_ctx: &PaintContext, sense: Sense) -> Response {
+        // HACK: write proper circuit interactables
+        let rect = paint_ctx.rect.expand(paint_ctx.screen.scale * -0.75);
+        paint_ctx
+            .ui
+            .interact(rect, paint_ctx.ui.auto_id_with(self.circuit.pos), sense)
+    }
+
+    /// Propagation delay of circuit outputs
+    pub fn delay(&self) -> SimTime {
+        self.circuit.delay()
+    }
+
+    /// Propagation delay of output connected to `wire`.
+    /// Zero inside compiled regions, which only delay signals leaving them
+    pub fn output_delay(&self, wire: Option<usize>) -> SimTime {
+        match wire {
+            Some(wire) if self.global_state.is_compiled_wire(wire) => 0,
+            _ => self.delay(),
+        }
+    }
 }
 
 #[allow(unused_variables)]
@@ -436,6 +599,13 @@ pub trait CircuitImpl: Send + Sync {
         None
     }
 
+    /// Whether outputs depend only on current inputs, without internal state,
+    /// timed updates or pin direction changes. With zero delay, such circuits
+    /// can be evaluated in compiled regions, see [`crate::board::CircuitBoard::set_compiled`]
+    fn is_combinational(&self) -> bool {
+        false
+    }
+
     /// Whether to automatically draw pins as small circuits
     fn draw_pin_points(&self) -> bool {
         true
@@ -527,7 +697,8 @@ impl CircuitPreview {
         }
     }
 
-    pub fn prop_changed(&self) {
+    pub fn prop_changed(&self, prop_id: &str) {
+        self.imp.prop_changed(&self.props, prop_id);
         *self.description.write() = self.imp.describe(&self.props);
     }
 
@@ -536,6 +707,7 @@ impl CircuitPreview {
     }
 }
 
+#[allow(unused_variables)]
 pub trait CircuitPreviewImpl {
     fn type_name(&self) -> DynStaticStr;
     fn display_name(&self) -> DynStaticStr;
@@ -547,6 +719,9 @@ pub trait CircuitPreviewImpl {
         data: &serde_intermediate::Intermediate,
     ) -> Option<Box<dyn CircuitPreviewImpl>>;
     fn default_props(&self) -> CircuitPropertyStore;
+
+    /// Called after a preview property was changed, can update dependent properties
+    fn prop_changed(&self, props: &CircuitPropertyStore, prop_id: &str) {}
 }
 
 #[derive(Default)]
@@ -562,6 +737,7 @@ pub struct CircuitPinDescription {
     pub dir: InternalPinDirection,
     pub name: DynStaticStr,
     pub pos: Vec2u,
+    pub width: u32,
 }
 
 #[derive(Clone)]
@@ -584,6 +760,7 @@ impl CircuitPinDescription {
             self.name.clone(),
             self.display_name.clone(),
             self.display_dir,
+            self.width,
         )
     }
 }
@@ -595,6 +772,55 @@ impl<const P: usize> CircuitDescription<P> {
             pins: Arc::new(self.pins.clone()),
         }
     }
+
+    /// Sets bit width of every pin
+    pub fn with_width(mut self, width: u32) -> Self {
+        for pin in self.pins.iter_mut() {
+            pin.width = width;
+        }
+        self
+    }
+}
+
+/// Reads "width" property, clamped to widths wires can carry
+pub fn read_width_prop(props: &CircuitPropertyStore) -> u32 {
+    props
+        .read_clone::<u32>("width")
+        .unwrap_or(1)
+        .clamp(1, WireState::MAX_WIDTH)
+}
+
+/// Runtime counterpart of [`describe_directional_circuit!`],
+/// for circuits which pin layout depends on properties.
+/// Pin positions and display directions are given for `default_dir`
+pub fn describe_directional_dyn(
+    default_dir: Direction4,
+    dir: Direction4,
+    size: [u32; 2],
+    pins: impl IntoIterator<Item = CircuitPinDescription>,
+) -> DynCircuitDescription {
+    let dir_normalized = dir.rotate_counterclockwise_by(default_dir);
+    let size_rotated = if default_dir.is_horizontal() == dir.is_horizontal() {
+        size
+    } else {
+        [size[1], size[0]]
+    };
+
+    let pins = pins
+        .into_iter()
+        .map(|mut pin| {
+            pin.display_dir = pin
+                .display_dir
+                .map(|d| d.rotate_clockwise_by(dir_normalized));
+            pin.pos = rotate_pos([pin.pos.x(), pin.pos.y()], size_rotated, dir_normalized).into();
+            pin
+        })
+        .collect();
+
+    DynCircuitDescription {
+        size: size_rotated.into(),
+        pins,
+    }
 }
 
 //  # - - - +  + - - +  + - - - +  + - - #
@@ -655,6 +881,7 @@ macro_rules! describe_directional_circuit {
                                 display_dir: Option::<Direction4>::from($pin_ddir)
                                     .map(|d| d.rotate_clockwise_by(dir_normalized)),
                                 pos: $crate::circuits::rotate_pos([$pin_x, $pin_y], size_rotated, dir_normalized).into(),
+                                width: 1,
                             },
                         )*
                     ]
@@ -711,6 +938,7 @@ macro_rules! describe_directional_custom_circuit {
                                 display_dir: Option::<Direction4>::from($pin_ddir)
                                     .map(|$dir_proc_param| $dir_proc_body.rotate_clockwise_by(dir_normalized)),
                                 pos: $crate::circuits::rotate_pos( { let $pos_proc_param = [$pin_x, $pin_y]; $pos_proc_body }, size_rotated, dir_normalized).into(),
+                                width: 1,
                             },
                         )*
                     ]
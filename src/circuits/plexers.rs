@@ -0,0 +1,452 @@
+use eframe::epaint::{Color32, FontId, PathShape, Stroke};
+use emath::{vec2, Align2, Pos2};
+
+use crate::{circuits::*, vector::Vec2f, Direction4};
+
+use super::props::CircuitProperty;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Multiplexer,
+    Demultiplexer,
+    Decoder,
+    PriorityEncoder,
+}
+
+impl Kind {
+    fn type_name(self) -> &'static str {
+        match self {
+            Kind::Multiplexer => "mux",
+            Kind::Demultiplexer => "demux",
+            Kind::Decoder => "decoder",
+            Kind::PriorityEncoder => "priority_encoder",
+        }
+    }
+
+    fn display_name(self) -> &'static str {
+        match self {
+            Kind::Multiplexer => "Multiplexer",
+            Kind::Demultiplexer => "Demultiplexer",
+            Kind::Decoder => "Decoder",
+            Kind::PriorityEncoder => "Priority encoder",
+        }
+    }
+
+    /// Whether data pins carry "width"-bit values
+    fn has_width(self) -> bool {
+        matches!(self, Kind::Multiplexer | Kind::Demultiplexer)
+    }
+
+    /// Whether circuit has a pin below its body
+    fn has_bottom_pin(self) -> bool {
+        self != Kind::Decoder
+    }
+}
+
+const MAX_SELECT_BITS: u32 = 5;
+
+/// Reads "select" property, clamped so there are at most 32 data pins
+fn read_select_prop(props: &CircuitPropertyStore) -> u32 {
+    props
+        .read_clone::<u32>("select")
+        .unwrap_or(1)
+        .clamp(1, MAX_SELECT_BITS)
+}
+
+/// Selected index, or state to output for a floating or invalid select
+fn read_select(state: WireState, bits: u32) -> Result<usize, WireState> {
+    let mut index = 0;
+    for i in 0..bits {
+        match state.bit(i) {
+            WireState::True => index |= 1 << i,
+            WireState::False => {}
+            _ => return Err(WireState::Error),
+        }
+    }
+    Ok(index)
+}
+
+fn pin(
+    name: impl Into<DynStaticStr>,
+    display_name: impl Into<DynStaticStr>,
+    dir: InternalPinDirection,
+    display_dir: Direction4,
+    pos: [u32; 2],
+    width: u32,
+) -> CircuitPinDescription {
+    CircuitPinDescription {
+        display_name: display_name.into(),
+        display_dir: Some(display_dir),
+        dir,
+        name: name.into(),
+        pos: pos.into(),
+        width,
+    }
+}
+
+struct Circuit {
+    kind: Kind,
+    inputs: Box<[CircuitPinInfo]>,
+    select: Option<CircuitPinInfo>,
+    outputs: Box<[CircuitPinInfo]>,
+    select_bits: u32,
+}
+
+impl Circuit {
+    fn new(kind: Kind) -> Self {
+        let mut circuit = Self {
+            kind,
+            inputs: Box::new([]),
+            select: None,
+            outputs: Box::new([]),
+            select_bits: 1,
+        };
+        circuit.set_pins(&Self::describe(kind, Direction4::Right, 1, 1));
+        circuit
+    }
+
+    /// Splits pins in the order `Circuit::describe` creates them
+    fn set_pins(&mut self, description: &DynCircuitDescription) {
+        let count = 1 << self.select_bits;
+        let (inputs, select) = match self.kind {
+            Kind::Multiplexer => (count, 1),
+            Kind::Demultiplexer => (1, 1),
+            Kind::Decoder => (0, 1),
+            Kind::PriorityEncoder => (count, 0),
+        };
+
+        let pins = &description.pins;
+        self.inputs = pins[..inputs].iter().map(|p| p.to_info()).collect();
+        self.select = pins[inputs..inputs + select].first().map(|p| p.to_info());
+        self.outputs = pins[inputs + select..]
+            .iter()
+            .map(|p| p.to_info())
+            .collect();
+    }
+
+    fn draw(
+        ctx: &PaintContext,
+        kind: Kind,
+        dir: Direction4,
+        select_bits: u32,
+        semi_transparent: bool,
+    ) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = Color32::from_gray(200).linear_multiply(opacity);
+        let stroke = Stroke::new(0.15 * ctx.screen.scale, border_color);
+
+        let angle = dir.inverted_ud().angle_to_right();
+        let count = (1u32 << select_bits) as f32;
+        let size = vec2(3.0, count + kind.has_bottom_pin() as u32 as f32);
+        let transformer = |x: f32, y: f32| -> Pos2 {
+            ctx.rect
+                .lerp_inside(Vec2f::from(vec2(x, y) / size).rotated_xy(angle, 0.5).into())
+        };
+
+        // Multiplexers are trapezoids narrowing towards their single-pin side
+        let (top, bottom) = match kind {
+            Kind::Multiplexer => ([0.1, 0.6], [count - 0.1, count - 0.6]),
+            Kind::Demultiplexer => ([0.6, 0.1], [count - 0.6, count - 0.1]),
+            Kind::Decoder | Kind::PriorityEncoder => ([0.1, 0.1], [count - 0.1, count - 0.1]),
+        };
+        let points = vec![
+            transformer(0.5, top[0]),
+            transformer(2.5, top[1]),
+            transformer(2.5, bottom[1]),
+            transformer(0.5, bottom[0]),
+        ];
+
+        if kind.has_bottom_pin() {
+            let body_bottom = (bottom[0] + bottom[1]) / 2.0;
+            ctx.paint.line_segment(
+                [transformer(1.5, body_bottom), transformer(1.5, count + 0.5)],
+                stroke,
+            );
+        }
+        ctx.paint.add(PathShape {
+            points,
+            closed: true,
+            fill: fill_color,
+            stroke,
+        });
+
+        let label = match kind {
+            Kind::Multiplexer => "MUX",
+            Kind::Demultiplexer => "DMX",
+            Kind::Decoder => "DEC",
+            Kind::PriorityEncoder => "PRI",
+        };
+        ctx.paint.text(
+            transformer(1.5, count / 2.0),
+            Align2::CENTER_CENTER,
+            label,
+            FontId::monospace(ctx.screen.scale * 0.5),
+            border_color,
+        );
+    }
+
+    fn describe_props(kind: Kind, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        let width = match kind.has_width() {
+            true => read_width_prop(props),
+            false => 1,
+        };
+        Self::describe(kind, dir, read_select_prop(props), width)
+    }
+
+    fn describe(
+        kind: Kind,
+        dir: Direction4,
+        select_bits: u32,
+        width: u32,
+    ) -> DynCircuitDescription {
+        let count = 1 << select_bits;
+        let middle = count / 2;
+        let input = InternalPinDirection::Inside;
+        let output = InternalPinDirection::Outside;
+
+        let indexed = |prefix: &'static str, dir, display_dir, x, width| {
+            (0..count).map(move |i| {
+                let name = format!("{prefix}_{i}");
+                pin(name, i.to_string(), dir, display_dir, [x, i], width)
+            })
+        };
+        let select = |display_dir, pos| pin("sel", "Select", input, display_dir, pos, select_bits);
+        let out = |width| pin("out", "Out", output, Direction4::Right, [2, middle], width);
+
+        let pins: Vec<_> = match kind {
+            Kind::Multiplexer => indexed("in", input, Direction4::Left, 0, width)
+                .chain([select(Direction4::Down, [1, count]), out(width)])
+                .collect(),
+            Kind::Demultiplexer => [
+                pin("in", "In", input, Direction4::Left, [0, middle], width),
+                select(Direction4::Down, [1, count]),
+            ]
+            .into_iter()
+            .chain(indexed("out", output, Direction4::Right, 2, width))
+            .collect(),
+            Kind::Decoder => std::iter::once(select(Direction4::Left, [0, middle]))
+                .chain(indexed("out", output, Direction4::Right, 2, 1))
+                .collect(),
+            Kind::PriorityEncoder => indexed("in", input, Direction4::Left, 0, 1)
+                .chain([
+                    out(select_bits),
+                    pin("valid", "Valid", output, Direction4::Down, [1, count], 1),
+                ])
+                .collect(),
+        };
+
+        let height = count + kind.has_bottom_pin() as u32;
+        describe_directional_dyn(Direction4::Right, dir, [3, height], pins)
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let dir = state_ctx
+            .props()
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right);
+        Circuit::draw(paint_ctx, self.kind, dir, self.select_bits, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Self::describe_props(self.kind, props);
+        self.select_bits = read_select_prop(props);
+        self.set_pins(&description);
+
+        let mut vec = self.inputs.to_vec();
+        vec.extend(self.select.iter().cloned());
+        vec.extend(self.outputs.iter().cloned());
+        vec.into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        // Priority encoder has no select pin and ignores this
+        let select = self
+            .select
+            .as_ref()
+            .map_or(WireState::None, |pin| pin.get_state(state_ctx));
+        let select = read_select(select, self.select_bits);
+        let low = |width| WireState::from_bits(width, std::iter::repeat(WireState::False));
+
+        match self.kind {
+            Kind::Multiplexer => {
+                let state = match select {
+                    Ok(index) => self.inputs[index].get_state(state_ctx),
+                    Err(state) => state,
+                };
+                self.outputs[0].set_state(state_ctx, state);
+            }
+            Kind::Demultiplexer | Kind::Decoder => {
+                let input = match self.kind {
+                    Kind::Demultiplexer => self.inputs[0].get_state(state_ctx),
+                    _ => WireState::True,
+                };
+                for (i, output) in self.outputs.iter().enumerate() {
+                    let state = match select {
+                        Ok(index) if index == i => input,
+                        Ok(_) => low(output.width),
+                        Err(state) => state,
+                    };
+                    output.set_state(state_ctx, state);
+                }
+            }
+            Kind::PriorityEncoder => {
+                // Highest active input wins, floating inputs are inactive
+                let mut selected = None;
+                for (i, input) in self.inputs.iter().enumerate().rev() {
+                    match input.get_state(state_ctx) {
+                        WireState::True => {
+                            selected = Some(Ok(i));
+                            break;
+                        }
+                        WireState::False | WireState::None => {}
+                        WireState::Error | WireState::Bus(_) => {
+                            selected = Some(Err(()));
+                            break;
+                        }
+                    }
+                }
+                let (out, valid) = match selected {
+                    Some(Ok(index)) => {
+                        let bits = (0..self.select_bits).map(|b| (index & (1 << b) != 0).into());
+                        (
+                            WireState::from_bits(self.select_bits, bits),
+                            WireState::True,
+                        )
+                    }
+                    Some(Err(())) => (WireState::Error, WireState::Error),
+                    None => (WireState::None, WireState::False),
+                };
+                self.outputs[0].set_state(state_ctx, out);
+                self.outputs[1].set_state(state_ctx, valid);
+            }
+        }
+    }
+
+    fn is_combinational(&self) -> bool {
+        true
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(self.kind, props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" | "select" => (true, true),
+            "width" => (false, true),
+            _ => (false, false),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {
+    pub kind: Kind,
+}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        self.kind.type_name().into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Circuit::draw(ctx, self.kind, dir, read_select_prop(props), in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new(self.kind))
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview { kind: self.kind }))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        let width = self
+            .kind
+            .has_width()
+            .then(|| CircuitProperty::new("width", "Width", 1u32));
+        CircuitPropertyStore::new(
+            [
+                CircuitProperty::new("dir", "Direction", Direction4::Right),
+                CircuitProperty::new("select", "Select bits", 1u32),
+            ]
+            .into_iter()
+            .chain(width),
+        )
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        self.kind.display_name().into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(self.kind, props)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::{
+        test::{create_state, drive, driven_board, pin_state},
+        WireState,
+    };
+
+    #[test]
+    fn plexer_select() {
+        let out = |board: &_, state: &_, pin| pin_state(board, state, 0, pin);
+
+        let board = driven_board("mux", "", &["in_0", "in_1", "sel"]);
+        let state = create_state(&board);
+        drive(&board, &state, 1, WireState::True);
+        assert_eq!(out(&board, &state, "out"), WireState::True);
+        drive(&board, &state, 3, WireState::True);
+        assert_eq!(out(&board, &state, "out"), WireState::False);
+        drive(&board, &state, 3, WireState::Error);
+        assert_eq!(out(&board, &state, "out"), WireState::Error);
+        // Floating select can't pick an input
+        let board = driven_board("mux", "", &["in_0", "in_1"]);
+        let state = create_state(&board);
+        assert_eq!(out(&board, &state, "out"), WireState::Error);
+
+        let board = driven_board("demux", "", &["in", "sel"]);
+        let state = create_state(&board);
+        drive(&board, &state, 1, WireState::True);
+        assert_eq!(out(&board, &state, "out_0"), WireState::True);
+        assert_eq!(out(&board, &state, "out_1"), WireState::False);
+        drive(&board, &state, 2, WireState::Error);
+        assert_eq!(out(&board, &state, "out_0"), WireState::Error);
+        assert_eq!(out(&board, &state, "out_1"), WireState::Error);
+        let board = driven_board("demux", "", &["in"]);
+        let state = create_state(&board);
+        assert_eq!(out(&board, &state, "out_0"), WireState::Error);
+
+        let board = driven_board("decoder", "", &["sel"]);
+        let state = create_state(&board);
+        assert_eq!(out(&board, &state, "out_0"), WireState::True);
+        drive(&board, &state, 1, WireState::Error);
+        assert_eq!(out(&board, &state, "out_0"), WireState::Error);
+        assert_eq!(out(&board, &state, "out_1"), WireState::Error);
+        let board = driven_board("decoder", "", &[]);
+        let state = create_state(&board);
+        assert_eq!(out(&board, &state, "out_1"), WireState::Error);
+
+        let board = driven_board("priority_encoder", "", &["in_0", "in_1"]);
+        let state = create_state(&board);
+        assert_eq!(out(&board, &state, "valid"), WireState::False);
+        drive(&board, &state, 1, WireState::True);
+        assert_eq!(out(&board, &state, "out"), WireState::False);
+        assert_eq!(out(&board, &state, "valid"), WireState::True);
+        drive(&board, &state, 2, WireState::Error);
+        assert_eq!(out(&board, &state, "out"), WireState::Error);
+        assert_eq!(out(&board, &state, "valid"), WireState::Error);
+    }
+}
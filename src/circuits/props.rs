@@ -3,9 +3,16 @@ use std::{
     collections::HashMap, ops::Deref,
 };
 
-use eframe::egui::{Ui, ComboBox};
+use eframe::{
+    egui::{Ui, ComboBox, DragValue},
+    epaint::Color32,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::{unwrap_option_or_return, Direction4, DynStaticStr, RwLock, unwrap_option_or_continue, ArcString};
+use crate::{
+    state::WireState, unwrap_option_or_return, Direction4, DynStaticStr, RwLock,
+    unwrap_option_or_continue, ArcString,
+};
 
 pub struct CircuitPropertyStore(RwLock<HashMap<DynStaticStr, CircuitProperty>>);
 
@@ -107,6 +114,7 @@ impl CircuitPropertyStore {
         [
             CircuitProperty::new("name", "Name", ArcString::default()),
             CircuitProperty::new("label_dir", "Label dir", Direction4::Down),
+            CircuitProperty::new("delay", "Delay", 1u32),
         ].into_iter()
     }
 }
@@ -365,4 +373,159 @@ impl CircuitPropertyImpl for ArcString {
             string.push_str(self.get_str().deref());
         }
     }
-}
\ No newline at end of file
+}
+impl CircuitPropertyImpl for u32 {
+    fn equals(&self, other: &dyn CircuitPropertyImpl) -> bool {
+        other.is_type_and(|o: &Self| o == self)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, not_equal: bool) -> Option<Box<dyn CircuitPropertyImpl>> {
+        let old = *self;
+        let drag = DragValue::new(self).speed(0.1);
+        let drag = if not_equal {
+            drag.custom_formatter(|_, _| "<many>".into())
+        } else {
+            drag
+        };
+        ui.add(drag)
+            .changed()
+            .then(|| Box::new(old) as Box<dyn CircuitPropertyImpl>)
+    }
+
+    fn clone(&self) -> Box<dyn CircuitPropertyImpl> {
+        Box::new(*self)
+    }
+
+    fn load(&mut self, data: &serde_intermediate::Intermediate) {
+        if let Ok(d) = serde_intermediate::de::intermediate::deserialize(data) {
+            *self = d;
+        }
+    }
+
+    fn save(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap_or_default()
+    }
+
+    fn copy_into(&self, other: &mut dyn CircuitPropertyImpl) {
+        if let Some(r) = other.downcast_mut() {
+            *r = *self;
+        }
+    }
+}
+
+impl CircuitPropertyImpl for Color32 {
+    fn equals(&self, other: &dyn CircuitPropertyImpl) -> bool {
+        other.is_type_and(|o: &Self| o == self)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, _: bool) -> Option<Box<dyn CircuitPropertyImpl>> {
+        let old = *self;
+        ui.color_edit_button_srgba(self)
+            .changed()
+            .then(|| Box::new(old) as Box<dyn CircuitPropertyImpl>)
+    }
+
+    fn clone(&self) -> Box<dyn CircuitPropertyImpl> {
+        Box::new(*self)
+    }
+
+    fn load(&mut self, data: &serde_intermediate::Intermediate) {
+        if let Ok(d) = serde_intermediate::de::intermediate::deserialize(data) {
+            *self = d;
+        }
+    }
+
+    fn save(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap_or_default()
+    }
+
+    fn copy_into(&self, other: &mut dyn CircuitPropertyImpl) {
+        if let Some(r) = other.downcast_mut() {
+            *r = *self;
+        }
+    }
+}
+
+/// Input state that turns an output device on
+#[derive(Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ActiveLevel {
+    #[default]
+    High,
+    Low,
+}
+
+// Saved by variant name, since unit variants read back from RON as plain units
+impl Serialize for ActiveLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            ActiveLevel::High => "High",
+            ActiveLevel::Low => "Low",
+        }
+        .serialize(serializer)
+    }
+}
+
+impl ActiveLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            ActiveLevel::High => "Active high",
+            ActiveLevel::Low => "Active low",
+        }
+    }
+
+    /// None for states that are neither high nor low. Floating inputs are inactive
+    pub fn is_active(self, state: WireState) -> Option<bool> {
+        match state {
+            WireState::True => Some(self == ActiveLevel::High),
+            WireState::False => Some(self == ActiveLevel::Low),
+            WireState::None => Some(false),
+            WireState::Error | WireState::Bus(_) => None,
+        }
+    }
+}
+
+impl CircuitPropertyImpl for ActiveLevel {
+    fn equals(&self, other: &dyn CircuitPropertyImpl) -> bool {
+        other.is_type_and(|o: &Self| o == self)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, not_equal: bool) -> Option<Box<dyn CircuitPropertyImpl>> {
+        let old = *self;
+        let mut changed = false;
+        ui.skip_ahead_auto_ids(1);
+        ComboBox::from_id_source(ui.next_auto_id())
+            .selected_text(if not_equal { "" } else { self.name() })
+            .show_ui(ui, |ui| {
+                for level in [ActiveLevel::High, ActiveLevel::Low] {
+                    let res = ui.selectable_value(self, level, level.name());
+                    if res.changed() || res.clicked() {
+                        changed = true;
+                    }
+                }
+            });
+        changed.then(|| Box::new(old) as Box<dyn CircuitPropertyImpl>)
+    }
+
+    fn clone(&self) -> Box<dyn CircuitPropertyImpl> {
+        Box::new(*self)
+    }
+
+    fn load(&mut self, data: &serde_intermediate::Intermediate) {
+        if let Ok(d) = serde_intermediate::de::intermediate::deserialize(data) {
+            *self = d;
+        }
+    }
+
+    fn save(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap_or_default()
+    }
+
+    fn copy_into(&self, other: &mut dyn CircuitPropertyImpl) {
+        if let Some(r) = other.downcast_mut() {
+            *r = *self;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use eframe::epaint::Stroke;
+
+use crate::circuits::{props::CircuitProperty, *};
+
+use super::props::CircuitPropertyStore;
+
+struct Circuit {
+    pin: CircuitPinInfo,
+    value: bool,
+    width: u32,
+}
+
+impl Circuit {
+    fn new(value: bool) -> Self {
+        let description = Self::describe();
+        Self {
+            pin: description.pins[0].to_info(),
+            value,
+            width: 1,
+        }
+    }
+
+    fn draw(ctx: &PaintContext, value: bool, semi_transparent: bool) {
+        let color_mul = if semi_transparent { 0.5 } else { 1.0 };
+        let color = WireState::from(value).color().linear_multiply(color_mul);
+        ctx.paint.circle_stroke(
+            ctx.rect.center(),
+            ctx.screen.scale * 0.5,
+            Stroke::new(1.0, color),
+        )
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<1> {
+        Self::describe().with_width(read_width_prop(props))
+    }
+
+    fn describe() -> CircuitDescription<1> {
+        CircuitDescription {
+            size: [1, 1].into(),
+            pins: [CircuitPinDescription {
+                display_name: "".into(),
+                display_dir: None,
+                dir: InternalPinDirection::Weak,
+                name: "pin".into(),
+                pos: [0, 0].into(),
+                width: 1,
+            }],
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, _: &CircuitStateContext, paint_ctx: &PaintContext) {
+        Circuit::draw(paint_ctx, self.value, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.pin = description.pins[0].to_info();
+        vec![self.pin.clone()].into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        let bits = std::iter::repeat(WireState::from(self.value));
+        self.pin
+            .set_state(state_ctx, WireState::from_bits(self.width, bits));
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, _: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "width" {
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.width = read_width_prop(props);
+    }
+}
+
+/// Weakly drives its wire high or low, stronger drivers override it
+#[derive(Debug)]
+pub struct Preview {
+    pub value: bool,
+}
+
+impl CircuitPreviewImpl for Preview {
+    fn draw_preview(&self, _: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        Circuit::draw(ctx, self.value, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new(self.value))
+    }
+
+    fn type_name(&self) -> DynStaticStr {
+        match self.value {
+            true => "pullup".into(),
+            false => "pulldown".into(),
+        }
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview { value: self.value }))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([CircuitProperty::new("width", "Width", 1u32)])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        match self.value {
+            true => "Pullup".into(),
+            false => "Pulldown".into(),
+        }
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::{
+        test::{create_state, load_board, pin_state},
+        WireState,
+    };
+
+    #[test]
+    fn pull_bus() {
+        // 4-bit pullup and pulldown on the inputs of 4-bit not gates
+        let board = load_board(
+            r#"(
+            version: 1,
+            wires: [
+                Some((points: [
+                    ([0, 0], (pin: Some((name: "pin", circuit: 0)))),
+                    ([1, 0], (left: true, pin: Some((name: "in", circuit: 1)))),
+                ])),
+                Some((points: [
+                    ([0, 2], (pin: Some((name: "pin", circuit: 2)))),
+                    ([1, 2], (left: true, pin: Some((name: "in", circuit: 3)))),
+                ])),
+            ],
+            circuits: [
+                Some((ty: "pullup", pos: [0, 0], pin_wires: [("pin", 0)], props: ({"width": 4}))),
+                Some((ty: "not", pos: [1, 0], pin_wires: [("in", 0)], props: ({"width": 4}))),
+                Some((ty: "pulldown", pos: [0, 2], pin_wires: [("pin", 1)], props: ({"width": 4}))),
+                Some((ty: "not", pos: [1, 2], pin_wires: [("in", 1)], props: ({"width": 4}))),
+            ],
+            states: [],
+        )"#,
+        );
+        let state = create_state(&board);
+        let bus = |value| WireState::from_bits(4, std::iter::repeat(WireState::from(value)));
+
+        assert!(state.diagnostics().is_empty());
+        assert_eq!(state.wires.get(0), bus(true));
+        assert_eq!(pin_state(&board, &state, 1, "out"), bus(false));
+        assert_eq!(state.wires.get(1), bus(false));
+        assert_eq!(pin_state(&board, &state, 3, "out"), bus(true));
+    }
+}
@@ -0,0 +1,147 @@
+use eframe::epaint::Color32;
+
+use crate::{
+    circuits::{
+        led::draw_led,
+        props::{ActiveLevel, CircuitProperty},
+        *,
+    },
+    describe_directional_circuit,
+    vector::Vec2f,
+    Direction4,
+};
+
+struct Circuit {
+    inputs: [CircuitPinInfo; 3],
+    dir: Direction4,
+    level: ActiveLevel,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            inputs: description.pins.each_ref().map(|pin| pin.to_info()),
+            dir: Direction4::Right,
+            level: ActiveLevel::High,
+        }
+    }
+
+    /// `channels` are red, green and blue, None if any of them is invalid
+    fn draw(ctx: &PaintContext, angle: f32, channels: Option<[bool; 3]>, semi_transparent: bool) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+        let center = ctx
+            .rect
+            .lerp_inside(Vec2f::from([0.75, 0.5]).rotated_xy(angle, 0.5).into());
+
+        let (color, lit) = match channels {
+            Some([false, false, false]) => (Color32::WHITE, Some(false)),
+            Some(channels) => {
+                let [r, g, b] = channels.map(|on| if on { 255 } else { 0 });
+                (Color32::from_rgb(r, g, b), Some(true))
+            }
+            None => (Color32::WHITE, None),
+        };
+        draw_led(ctx, center, ctx.screen.scale * 0.45, color, lit, opacity);
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<3> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<3> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [2, 3],
+
+            "r": Inside, "R", Left, [0, 0],
+            "g": Inside, "G", Left, [0, 1],
+            "b": Inside, "B", Left, [0, 2]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let angle = self.dir.inverted_ud().angle_to_right();
+        let channels = self
+            .inputs
+            .each_ref()
+            .map(|pin| self.level.is_active(pin.get_state(state_ctx)));
+        let channels = channels
+            .iter()
+            .all(Option::is_some)
+            .then(|| channels.map(|c| c.unwrap_or_default()));
+        Circuit::draw(paint_ctx, angle, channels, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.inputs = description.pins.each_ref().map(|pin| pin.to_info());
+        self.inputs.to_vec().into_boxed_slice()
+    }
+
+    fn update_signals(&self, _: &CircuitStateContext, _: Option<usize>) {}
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *resize = true;
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        self.level = props.read_clone("level").unwrap_or_default();
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "rgb_led".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let angle = props
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        Circuit::draw(ctx, angle, Some([true, false, true]), in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("level", "Level", ActiveLevel::High),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "RGB LED".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
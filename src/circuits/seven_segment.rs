@@ -0,0 +1,203 @@
+use eframe::epaint::{Color32, Rounding, Stroke};
+use emath::{vec2, Pos2, Vec2};
+
+use crate::{
+    circuits::{
+        props::{ActiveLevel, CircuitProperty},
+        *,
+    },
+    describe_directional_circuit,
+    vector::Vec2f,
+    Direction4,
+};
+
+/// Segment ends in a unit box, in order a, b, c, d, e, f, g
+const SEGMENTS: [[[f32; 2]; 2]; 7] = [
+    [[0.15, 0.0], [0.85, 0.0]],
+    [[1.0, 0.07], [1.0, 0.43]],
+    [[1.0, 0.57], [1.0, 0.93]],
+    [[0.15, 1.0], [0.85, 1.0]],
+    [[0.0, 0.57], [0.0, 0.93]],
+    [[0.0, 0.07], [0.0, 0.43]],
+    [[0.15, 0.5], [0.85, 0.5]],
+];
+
+const LIT_COLOR: Color32 = Color32::from_rgb(255, 40, 40);
+const UNLIT_COLOR: Color32 = Color32::from_rgb(70, 30, 30);
+
+/// Draws a digit display over circuit of `size` facing right, upright when `dir` is right.
+/// `segments` are a to g, then decimal point; None for invalid input
+pub(super) fn draw_display(
+    ctx: &PaintContext,
+    size: Vec2u,
+    dir: Direction4,
+    segments: [Option<bool>; 8],
+    semi_transparent: bool,
+) {
+    let opacity = if semi_transparent { 0.6 } else { 1.0 };
+    let angle = dir.inverted_ud().angle_to_right();
+    let size: Vec2 = size.convert(|v| v as f32).into();
+
+    ctx.paint.rect(
+        ctx.rect.shrink(ctx.screen.scale * 0.3),
+        Rounding::same(ctx.screen.scale * 0.15),
+        Color32::from_gray(30).linear_multiply(opacity),
+        Stroke::new(
+            0.15 * ctx.screen.scale,
+            Color32::BLACK.linear_multiply(opacity),
+        ),
+    );
+
+    // Digit box within unrotated circuit, leaving room for pins
+    let digit_min = vec2(0.9, 1.2);
+    let digit_size = vec2(size.x - 2.4, size.y - 2.4);
+    let transformer = |p: [f32; 2]| -> Pos2 {
+        let p = digit_min + vec2(p[0], p[1]) * digit_size;
+        ctx.rect
+            .lerp_inside(Vec2f::from(p / size).rotated_xy(angle, 0.5).into())
+    };
+    let color = |segment: Option<bool>| {
+        match segment {
+            Some(true) => LIT_COLOR,
+            Some(false) => UNLIT_COLOR,
+            None => WireState::Error.color(),
+        }
+        .linear_multiply(opacity)
+    };
+
+    for (ends, segment) in SEGMENTS.iter().zip(segments) {
+        ctx.paint.line_segment(
+            ends.map(transformer),
+            Stroke::new(0.3 * ctx.screen.scale, color(segment)),
+        );
+    }
+    ctx.paint.circle_filled(
+        transformer([1.35, 1.0]),
+        0.2 * ctx.screen.scale,
+        color(segments[7]),
+    );
+}
+
+struct Circuit {
+    inputs: [CircuitPinInfo; 8],
+    dir: Direction4,
+    level: ActiveLevel,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            inputs: description.pins.each_ref().map(|pin| pin.to_info()),
+            dir: Direction4::Right,
+            level: ActiveLevel::High,
+        }
+    }
+
+    fn draw(
+        ctx: &PaintContext,
+        dir: Direction4,
+        segments: [Option<bool>; 8],
+        semi_transparent: bool,
+    ) {
+        let size = Self::describe(Direction4::Right).size;
+        draw_display(ctx, size, dir, segments, semi_transparent);
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<8> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<8> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [4, 6],
+
+            "a": Inside, "A", Up, [0, 0],
+            "b": Inside, "B", Up, [1, 0],
+            "c": Inside, "C", Up, [2, 0],
+            "d": Inside, "D", Up, [3, 0],
+            "e": Inside, "E", Down, [0, 5],
+            "f": Inside, "F", Down, [1, 5],
+            "g": Inside, "G", Down, [2, 5],
+            "dp": Inside, "DP", Down, [3, 5]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let segments = self
+            .inputs
+            .each_ref()
+            .map(|pin| self.level.is_active(pin.get_state(state_ctx)));
+        Circuit::draw(paint_ctx, self.dir, segments, false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.inputs = description.pins.each_ref().map(|pin| pin.to_info());
+        self.inputs.to_vec().into_boxed_slice()
+    }
+
+    fn update_signals(&self, _: &CircuitStateContext, _: Option<usize>) {}
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *resize = true;
+            *recreate_pins = true;
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        self.level = props.read_clone("level").unwrap_or_default();
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "seven_segment".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        // Shows 8.
+        Circuit::draw(ctx, dir, [Some(true); 8], in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("level", "Level", ActiveLevel::High),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "7-segment display".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
@@ -0,0 +1,170 @@
+use eframe::{
+    egui::{PointerButton, Sense},
+    epaint::{Color32, Rounding, Stroke},
+};
+use emath::{vec2, Rect};
+
+use crate::{describe_directional_circuit, Direction4};
+
+use super::{props::CircuitProperty, *};
+
+/// Latching switch, keeps its state until clicked again
+struct Circuit {
+    out_pin: CircuitPinInfo,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            out_pin: description.pins[0].to_info(),
+        }
+    }
+
+    /// Track runs along `dir`, knob moves towards the output when on
+    fn draw(ctx: &PaintContext, dir: Direction4, on: bool, semi_transparent: bool) {
+        let color_mul = if semi_transparent { 0.5 } else { 1.0 };
+
+        let size = match dir.is_horizontal() {
+            true => vec2(1.6, 0.8),
+            false => vec2(0.8, 1.6),
+        };
+        let track = Rect::from_center_size(ctx.rect.center(), size * ctx.screen.scale);
+        ctx.paint.rect(
+            track,
+            Rounding::same(ctx.screen.scale * 0.4),
+            WireState::from(on).color().linear_multiply(color_mul),
+            Stroke::new(
+                0.1 * ctx.screen.scale,
+                Color32::from_gray(60).linear_multiply(color_mul),
+            ),
+        );
+
+        let dir = dir.unit_vector();
+        let offset = vec2(dir.x() as f32, dir.y() as f32) * ctx.screen.scale * 0.4;
+        let knob = match on {
+            true => track.center() + offset,
+            false => track.center() - offset,
+        };
+        ctx.paint.circle_filled(
+            knob,
+            ctx.screen.scale * 0.3,
+            Color32::from_gray(230).linear_multiply(color_mul),
+        );
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<1> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Self::describe(dir)
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<1> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [3, 3],
+
+            "out": Outside, "Out", Right, [2, 1]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let on = state_ctx
+            .read_circuit_internal_state::<State, _>(|state| state.on)
+            .unwrap_or_default();
+        let dir = state_ctx.props().read_clone("dir").unwrap_or(Direction4::Right);
+        Self::draw(paint_ctx, dir, on, false);
+
+        let interaction = state_ctx.interact(paint_ctx, Sense::click());
+        if interaction.clicked_by(PointerButton::Primary) {
+            let on = state_ctx.write_circuit_internal_state::<State, _>(|s| {
+                s.on = !s.on;
+                s.on
+            });
+            self.out_pin.set_state(state_ctx, on.into());
+        }
+    }
+
+    fn prop_changed(&self, prop_id: &str, _: &mut bool, recreate_pins: &mut bool) {
+        if prop_id == "dir" {
+            *recreate_pins = true
+        }
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Self::describe_props(props);
+        self.out_pin = description.pins[0].to_info();
+
+        vec![self.out_pin.clone()].into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        let on = state_ctx
+            .read_circuit_internal_state::<State, _>(|state| state.on)
+            .unwrap_or_default();
+        self.out_pin.set_state(state_ctx, on.into());
+    }
+
+    fn load_internal(
+        &self,
+        data: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn InternalCircuitState>> {
+        serde_intermediate::de::intermediate::deserialize::<State>(data)
+            .ok()
+            .map(|s| Box::new(s) as Box<dyn InternalCircuitState>)
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    on: bool,
+}
+
+impl InternalCircuitState for State {
+    fn serialize(&self) -> serde_intermediate::Intermediate {
+        serde_intermediate::to_intermediate(self).unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        Circuit::draw(ctx, dir, false, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn type_name(&self) -> DynStaticStr {
+        "switch".into()
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([CircuitProperty::new("dir", "Direction", Direction4::Right)])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "Toggle switch".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
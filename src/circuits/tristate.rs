@@ -0,0 +1,177 @@
+use eframe::epaint::{Color32, PathShape, Stroke};
+use emath::{pos2, vec2, Pos2};
+
+use crate::{
+    circuits::{props::CircuitProperty, *},
+    describe_directional_circuit,
+    vector::Vec2f,
+    Direction4,
+};
+
+struct Circuit {
+    dir: Direction4,
+    input: CircuitPinInfo,
+    output: CircuitPinInfo,
+    enable: CircuitPinInfo,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        let description = Self::describe(Direction4::Right);
+        Self {
+            input: description.pins[0].to_info(),
+            output: description.pins[1].to_info(),
+            enable: description.pins[2].to_info(),
+            dir: Direction4::Right,
+        }
+    }
+
+    /// `enable` is None for previews
+    fn draw(ctx: &PaintContext, angle: f32, enable: Option<WireState>, semi_transparent: bool) {
+        let opacity = if semi_transparent { 0.6 } else { 1.0 };
+
+        let border_color = Color32::BLACK.linear_multiply(opacity);
+        let fill_color = match enable {
+            Some(WireState::True) | None => Color32::from_gray(200),
+            // Output is released
+            Some(_) => Color32::from_gray(130),
+        }
+        .linear_multiply(opacity);
+        let enable_color = enable
+            .unwrap_or(WireState::False)
+            .color()
+            .linear_multiply(opacity);
+
+        let size = vec2(2.0, 2.0);
+        let transformer = |p: Pos2| {
+            ctx.rect.lerp_inside(
+                Vec2f::from(p.to_vec2() / size)
+                    .rotated_xy(angle, 0.5)
+                    .into(),
+            )
+        };
+
+        ctx.paint.line_segment(
+            [transformer(pos2(1.0, 0.7)), transformer(pos2(1.0, 1.5))],
+            Stroke::new(0.15 * ctx.screen.scale, enable_color),
+        );
+
+        let points = vec![
+            transformer(pos2(0.5, 0.1)),
+            transformer(pos2(1.5, 0.5)),
+            transformer(pos2(0.5, 0.9)),
+        ];
+        ctx.paint.add(PathShape {
+            points,
+            closed: true,
+            fill: fill_color,
+            stroke: Stroke::new(0.15 * ctx.screen.scale, border_color),
+        });
+    }
+
+    fn describe_props(props: &CircuitPropertyStore) -> CircuitDescription<3> {
+        let dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+        let width = read_width_prop(props);
+        let mut description = Self::describe(dir);
+        description.pins[0].width = width;
+        description.pins[1].width = width;
+        description
+    }
+
+    fn describe(dir: Direction4) -> CircuitDescription<3> {
+        describe_directional_circuit! {
+            default_dir: Right,
+            dir: dir,
+            size: [2, 2],
+
+            "in": Inside, "In", Left, [0, 0],
+            "out": StateDependent { default: PinDirection::Inside }, "Out", Right, [1, 0],
+            "en": Inside, "Enable", Down, [1, 1]
+        }
+    }
+}
+
+impl CircuitImpl for Circuit {
+    fn draw(&self, state_ctx: &CircuitStateContext, paint_ctx: &PaintContext) {
+        let angle = self.dir.inverted_ud().angle_to_right();
+        let enable = self.enable.get_state(state_ctx);
+        Circuit::draw(paint_ctx, angle, Some(enable), false);
+    }
+
+    fn create_pins(&mut self, props: &CircuitPropertyStore) -> Box<[CircuitPinInfo]> {
+        let description = Circuit::describe_props(props);
+        self.input = description.pins[0].to_info();
+        self.output = description.pins[1].to_info();
+        self.enable = description.pins[2].to_info();
+        vec![self.input.clone(), self.output.clone(), self.enable.clone()].into_boxed_slice()
+    }
+
+    fn update_signals(&self, state_ctx: &CircuitStateContext, _: Option<usize>) {
+        let (output, dir) = match self.enable.get_state(state_ctx) {
+            WireState::True => (self.input.get_state(state_ctx), PinDirection::Outside),
+            // Output stops driving the wire and reads it instead
+            WireState::False | WireState::None => (WireState::None, PinDirection::Inside),
+            WireState::Error | WireState::Bus(_) => (WireState::Error, PinDirection::Outside),
+        };
+        self.output.set_state_and_direction(state_ctx, output, dir);
+    }
+
+    fn size(&self, props: &CircuitPropertyStore) -> Vec2u {
+        Self::describe_props(props).size
+    }
+
+    fn prop_changed(&self, prop_id: &str, resize: &mut bool, recreate_pins: &mut bool) {
+        (*resize, *recreate_pins) = match prop_id {
+            "dir" => (true, true),
+            "width" => (false, true),
+            _ => (false, false),
+        }
+    }
+
+    fn apply_props(&mut self, props: &CircuitPropertyStore, _: Option<&str>) {
+        self.dir = props.read_clone("dir").unwrap_or(Direction4::Right);
+    }
+}
+
+pub struct Preview {}
+
+impl CircuitPreviewImpl for Preview {
+    fn type_name(&self) -> DynStaticStr {
+        "tristate".into()
+    }
+
+    fn draw_preview(&self, props: &CircuitPropertyStore, ctx: &PaintContext, in_world: bool) {
+        let angle = props
+            .read_clone("dir")
+            .unwrap_or(Direction4::Right)
+            .inverted_ud()
+            .angle_to_right();
+        Circuit::draw(ctx, angle, None, in_world);
+    }
+
+    fn create_impl(&self) -> Box<dyn CircuitImpl> {
+        Box::new(Circuit::new())
+    }
+
+    fn load_impl_data(
+        &self,
+        _: &serde_intermediate::Intermediate,
+    ) -> Option<Box<dyn CircuitPreviewImpl>> {
+        Some(Box::new(Preview {}))
+    }
+
+    fn default_props(&self) -> CircuitPropertyStore {
+        CircuitPropertyStore::new([
+            CircuitProperty::new("dir", "Direction", Direction4::Right),
+            CircuitProperty::new("width", "Width", 1u32),
+        ])
+    }
+
+    fn display_name(&self) -> DynStaticStr {
+        "Tri-state buffer".into()
+    }
+
+    fn describe(&self, props: &CircuitPropertyStore) -> DynCircuitDescription {
+        Circuit::describe_props(props).to_dyn()
+    }
+}
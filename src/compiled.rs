@@ -0,0 +1,281 @@
+use crate::{
+    board::CircuitBoard,
+    circuits::{Circuit, InternalPinDirection},
+};
+
+/// Evaluation order of combinational circuits that can be updated in a single pass,
+/// see [`CircuitBoard::set_compiled`]
+pub struct CompiledRegions {
+    /// Sim lock generation regions were built at
+    pub(crate) generation: u64,
+
+    /// Rank of each compiled circuit, indexed by circuit id.
+    /// Circuits only drive circuits of higher rank
+    ranks: Vec<Option<u32>>,
+    /// Whether every pin on a wire belongs to a compiled circuit, indexed by wire id
+    internal_wires: Vec<bool>,
+    circuits: usize,
+}
+
+impl CompiledRegions {
+    pub fn build(board: &CircuitBoard, generation: u64) -> Self {
+        let count = board.circuits.inner().len();
+        let mut compilable = vec![false; count];
+        for circuit in board.circuits.iter() {
+            compilable[circuit.id] = is_compilable(circuit);
+        }
+
+        let mut edges = vec![vec![]; count];
+        for wire in board.wires.iter() {
+            let mut drivers = vec![];
+            let mut readers = vec![];
+            for point in wire.points.values() {
+                let pin = unwrap_option_or_continue!(&point.pin).read();
+                let circuit = pin.id.circuit_id;
+                if !compilable.get(circuit).copied().unwrap_or(false) {
+                    continue;
+                }
+                match pin.dir {
+                    InternalPinDirection::Outside => drivers.push(circuit),
+                    InternalPinDirection::Inside => readers.push(circuit),
+                    _ => {}
+                }
+            }
+            for driver in drivers {
+                edges[driver].extend_from_slice(&readers);
+            }
+        }
+
+        let mut ranks = levelize(&edges);
+        for (rank, compilable) in ranks.iter_mut().zip(compilable) {
+            if !compilable {
+                *rank = None;
+            }
+        }
+        let circuits = ranks.iter().filter(|r| r.is_some()).count();
+
+        let mut internal_wires = vec![false; board.wires.inner().len()];
+        for wire in board.wires.iter() {
+            internal_wires[wire.id] = wire.points.values().all(|point| match &point.pin {
+                Some(pin) => ranks.get(pin.read().id.circuit_id).copied().flatten().is_some(),
+                None => true,
+            });
+        }
+
+        Self {
+            generation,
+            ranks,
+            internal_wires,
+            circuits,
+        }
+    }
+
+    /// None if circuit is updated through the event queue
+    pub fn rank(&self, circuit: usize) -> Option<u32> {
+        self.ranks.get(circuit).copied().flatten()
+    }
+
+    /// Whether wire only connects compiled circuits, so it changes without delay
+    pub fn is_internal_wire(&self, wire: usize) -> bool {
+        self.internal_wires.get(wire).copied().unwrap_or(false)
+    }
+
+    /// Number of compiled circuits
+    pub fn circuits(&self) -> usize {
+        self.circuits
+    }
+}
+
+/// Combinational circuits with fixed pin directions.
+/// Anything else needs the event queue to keep its timing
+fn is_compilable(circuit: &Circuit) -> bool {
+    circuit.imp.read().is_combinational()
+        && circuit.info.read().pins.iter().all(|info| {
+            matches!(
+                info.pin.read().dir,
+                InternalPinDirection::Inside | InternalPinDirection::Outside
+            )
+        })
+}
+
+/// Ranks graph nodes so every edge goes from a lower rank to a higher one.
+/// Nodes on cycles get no rank and edges from them are ignored
+fn levelize(edges: &[Vec<usize>]) -> Vec<Option<u32>> {
+    let cyclic = cyclic_nodes(edges);
+
+    let mut indegree = vec![0usize; edges.len()];
+    for (_, targets) in edges.iter().enumerate().filter(|(node, _)| !cyclic[*node]) {
+        for &target in targets.iter().filter(|t| !cyclic[**t]) {
+            indegree[target] += 1;
+        }
+    }
+
+    let mut level = vec![0u32; edges.len()];
+    let mut ranks = vec![None; edges.len()];
+    let mut ready: Vec<_> = (0..edges.len())
+        .filter(|n| !cyclic[*n] && indegree[*n] == 0)
+        .collect();
+    while let Some(node) = ready.pop() {
+        ranks[node] = Some(level[node]);
+        for &target in edges[node].iter().filter(|t| !cyclic[**t]) {
+            level[target] = level[target].max(level[node] + 1);
+            indegree[target] -= 1;
+            if indegree[target] == 0 {
+                ready.push(target);
+            }
+        }
+    }
+    ranks
+}
+
+/// Nodes that can reach themselves, found with Tarjan's strongly connected components algorithm
+fn cyclic_nodes(edges: &[Vec<usize>]) -> Vec<bool> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut index = vec![UNVISITED; edges.len()];
+    let mut lowlink = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut cyclic = vec![false; edges.len()];
+    let mut stack = vec![];
+    let mut next_index = 0;
+
+    // Nodes being visited and their next edge, instead of recursion that large boards overflow
+    let mut calls: Vec<(usize, usize)> = vec![];
+
+    for root in 0..edges.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        calls.push((root, 0));
+        while let Some((node, edge)) = calls.last_mut() {
+            let node = *node;
+            if *edge == 0 && index[node] == UNVISITED {
+                index[node] = next_index;
+                lowlink[node] = next_index;
+                next_index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            }
+
+            if let Some(&target) = edges[node].get(*edge) {
+                *edge += 1;
+                if index[target] == UNVISITED {
+                    calls.push((target, 0));
+                } else if on_stack[target] {
+                    lowlink[node] = lowlink[node].min(index[target]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some((parent, _)) = calls.last() {
+                lowlink[*parent] = lowlink[*parent].min(lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                let start = stack.iter().rposition(|n| *n == node).unwrap_or(0);
+                let cycle = stack.len() - start > 1 || edges[node].contains(&node);
+                for n in stack.drain(start..) {
+                    on_stack[n] = false;
+                    cyclic[n] = cycle;
+                }
+            }
+        }
+    }
+    cyclic
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{
+        test::{create_state, drive, load_board},
+        WireState,
+    };
+
+    /// a, b -> and -> not -> xor <- a, every circuit with default delay
+    const GATES: &str = r#"(
+        wires: [
+            Some((points: [
+                ([1, 0], (pin: Some((name: "out", circuit: 0)))),
+                ([2, 0], (left: true)),
+                ([3, 0], (left: true, pin: Some((name: "in_0", circuit: 2)))),
+                ([2, 4], (up: true)),
+                ([7, 4], (left: true, pin: Some((name: "in_1", circuit: 4)))),
+            ])),
+            Some((points: [
+                ([1, 2], (pin: Some((name: "out", circuit: 1)))),
+                ([3, 2], (left: true, pin: Some((name: "in_1", circuit: 2)))),
+            ])),
+            Some((points: [
+                ([4, 1], (pin: Some((name: "out", circuit: 2)))),
+                ([5, 1], (left: true, pin: Some((name: "in", circuit: 3)))),
+            ])),
+            Some((points: [
+                ([6, 1], (pin: Some((name: "out", circuit: 3)))),
+                ([7, 1], (left: true)),
+                ([7, 3], (up: true, pin: Some((name: "in_0", circuit: 4)))),
+            ])),
+            Some((points: [
+                ([8, 3], (pin: Some((name: "out", circuit: 4)))),
+                ([9, 3], (left: true)),
+            ])),
+        ],
+        circuits: [
+            Some((ty: "button", pos: [0, 0], pin_wires: [("out", 0)], props: ({}))),
+            Some((ty: "button", pos: [0, 2], pin_wires: [("out", 1)], props: ({}))),
+            Some((ty: "and", pos: [3, 0], pin_wires: [("in_0", 0), ("in_1", 1), ("out", 2)],
+                props: ({}))),
+            Some((ty: "not", pos: [5, 1], pin_wires: [("in", 2), ("out", 3)], props: ({}))),
+            Some((ty: "xor", pos: [7, 3], pin_wires: [("in_0", 3), ("in_1", 0), ("out", 4)],
+                props: ({}))),
+        ],
+        states: [],
+    )"#;
+
+    #[test]
+    fn compiled_matches_queue() {
+        let settled_wires = |compiled: bool| {
+            let board = load_board(GATES);
+            board.write().set_compiled(compiled, false);
+            if compiled {
+                let regions = board.read().compiled_regions().unwrap();
+                assert_eq!(regions.circuits(), 3);
+            }
+            let state = create_state(&board);
+
+            let mut wires = vec![];
+            for (a, b) in [(false, false), (true, false), (true, true), (false, true)] {
+                drive(&board, &state, 0, a.into());
+                drive(&board, &state, 1, b.into());
+                wires.push((0..5).map(|w| state.read_wire(w)).collect::<Vec<_>>());
+            }
+            wires
+        };
+
+        let queued = settled_wires(false);
+        assert_eq!(queued[2][4], WireState::True);
+        assert_eq!(settled_wires(true), queued);
+    }
+
+    #[test]
+    fn levelize_graph() {
+        // 0 -> 1 -> 3, 0 -> 2 -> 3, 4 <-> 5 -> 6, 7 -> 7
+        let edges = vec![
+            vec![1, 2],
+            vec![3],
+            vec![3],
+            vec![],
+            vec![5],
+            vec![4, 6],
+            vec![],
+            vec![7],
+        ];
+        let ranks = levelize(&edges);
+        assert_eq!(ranks[..4], [Some(0), Some(1), Some(1), Some(2)]);
+        assert_eq!(ranks[4], None);
+        assert_eq!(ranks[5], None);
+        // Only driven by a loop, so it's still compiled
+        assert_eq!(ranks[6], Some(0));
+        assert_eq!(ranks[7], None);
+    }
+}
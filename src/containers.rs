@@ -1,5 +1,8 @@
 use std::{
-    collections::{hash_map::RandomState, VecDeque, vec_deque},
+    collections::{
+        hash_map::{DefaultHasher, RandomState},
+        VecDeque, vec_deque,
+    },
     hash::{BuildHasher, Hasher},
     mem::MaybeUninit,
     ops::{Bound, Index, Range, RangeBounds},
@@ -778,8 +781,37 @@ impl<'a, const CHUNK_SIZE: usize, T: Default> Iterator for ChunksAreaIterator<'a
     }
 }
 
+/// Hasher builder that always starts from the same seed,
+/// so a [`RandomQueue`] using it picks items in a reproducible order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeededState {
+    pub seed: u64,
+}
+
+impl SeededState {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Seed that differs between runs
+    pub fn random_seed() -> u64 {
+        RandomState::new().build_hasher().finish()
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.seed);
+        hasher
+    }
+}
+
 pub struct RandomQueue<T, S: BuildHasher = RandomState> {
     vec: FixedVec<T>,
+    hash_builder: S,
     hasher: <S as BuildHasher>::Hasher,
 }
 
@@ -794,7 +826,7 @@ impl<T: Serialize, H: BuildHasher> Serialize for RandomQueue<T, H> {
 
 impl<T, S: Default + BuildHasher> Default for RandomQueue<T, S> {
     fn default() -> Self {
-        Self::with_hasher(&Default::default())
+        Self::with_hasher(Default::default())
     }
 }
 
@@ -816,20 +848,31 @@ impl<T> RandomQueue<T> {
             ..Default::default()
         }
     }
-
-    pub(crate) fn drain(&mut self) -> FixedVecDrain<'_, T> {
-        self.vec.drain(..)
-    }
 }
 
 impl<T, S: BuildHasher> RandomQueue<T, S> {
-    pub fn with_hasher(hash_builder: &S) -> Self {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from_option_vec_with_hasher(vec![], hash_builder)
+    }
+
+    pub fn from_option_vec_with_hasher(vec: Vec<Option<T>>, hash_builder: S) -> Self {
         Self {
-            vec: vec![].into(),
+            vec: FixedVec::from_option_vec(vec),
             hasher: hash_builder.build_hasher(),
+            hash_builder,
         }
     }
 
+    pub(crate) fn drain(&mut self) -> FixedVecDrain<'_, T> {
+        self.vec.drain(..)
+    }
+
+    /// Replaces hasher, restarting the sequence items are picked in
+    pub fn set_hasher(&mut self, hash_builder: S) {
+        self.hasher = hash_builder.build_hasher();
+        self.hash_builder = hash_builder;
+    }
+
     pub fn enqueue(&mut self, value: T) {
         let pos = self.vec.first_free_pos();
         self.vec.set(value, pos);
@@ -861,8 +904,10 @@ impl<T, S: BuildHasher> RandomQueue<T, S> {
         Some(item)
     }
 
+    /// Removes all items and restarts the sequence items are picked in
     pub fn clear(&mut self) {
         self.vec.clear();
+        self.hasher = self.hash_builder.build_hasher();
     }
 
     pub fn iter(&self) -> FixedVecIterator<'_, T> {
@@ -1077,19 +1122,56 @@ mod test {
         assert_eq!(buf[2], 4);
         assert_eq!(buf[3], 5);
     }
+
+    #[test]
+    fn seeded_queue_order() {
+        let run = |queue: &mut Queue<i32>| {
+            for v in 0..32 {
+                queue.enqueue(v);
+            }
+            let mut order = vec![];
+            while let Some(v) = queue.dequeue() {
+                order.push(v);
+                if v % 4 == 0 && v < 100 {
+                    queue.enqueue(v + 100);
+                }
+            }
+            order
+        };
+        let order = run(&mut Queue::new(vec![], false, 7));
+        assert_eq!(order, run(&mut Queue::new(vec![], false, 7)));
+        assert_ne!(order, run(&mut Queue::new(vec![], false, 8)));
+
+        // Clearing starts over from the seed
+        let mut queue = Queue::new(vec![], false, 7);
+        queue.enqueue(0);
+        queue.dequeue();
+        queue.clear();
+        assert_eq!(order, run(&mut queue));
+    }
 }
 
 
 pub enum Queue<T> {
-    Random(RandomQueue<T>),
+    Random(RandomQueue<T, SeededState>),
     Ordered(VecDeque<T>)
 }
 
 impl<T> Queue<T> {
-    pub fn new(vec: Vec<T>, ordered: bool) -> Self {
+    pub fn new(vec: Vec<T>, ordered: bool, seed: u64) -> Self {
         match ordered {
             true => Self::Ordered(VecDeque::from(vec)),
-            false => Self::Random(RandomQueue::from_vec(vec)),
+            false => {
+                let vec = vec.into_iter().map(Some).collect();
+                Self::Random(RandomQueue::from_option_vec_with_hasher(vec, SeededState::new(seed)))
+            }
+        }
+    }
+
+    /// Restarts random order from `seed`, ordered queue ignores it
+    pub fn set_seed(&mut self, seed: u64) {
+        if let Queue::Random(r) = self {
+            r.set_hasher(SeededState::new(seed));
         }
     }
 
@@ -1114,7 +1196,7 @@ impl<T> Queue<T> {
         }
     }
 
-    pub fn set_ordered(&mut self, ordered: bool) {
+    pub fn set_ordered(&mut self, ordered: bool, seed: u64) {
         if ordered {
             if let Queue::Random(r) = self {
                 let vec: Vec<T> = r.drain().collect();
@@ -1123,7 +1205,10 @@ impl<T> Queue<T> {
         }
         else if let Queue::Ordered(o) = self {
             let v = o.drain(..).map(Some).collect();
-            *self = Queue::Random(RandomQueue::from_option_vec(v))
+            *self = Queue::Random(RandomQueue::from_option_vec_with_hasher(
+                v,
+                SeededState::new(seed),
+            ))
         }
     }
 
@@ -1,11 +1,13 @@
-use std::{time::Duration, collections::HashMap};
+use std::{time::Duration, collections::HashMap, ops::Deref};
 
 use serde::{Deserialize, Serialize};
 use serde_intermediate::Intermediate;
 
 use crate::{
+    breakpoint::Breakpoint,
     circuits::{PinDirection, CircuitPreview},
-    state::{UpdateTask, WireState},
+    containers::SeededState,
+    state::{ClockMode, SimTime, UpdateTask, WireState},
     vector::{Vec2i, Vec2u}, DynStaticStr, Direction2,
 };
 
@@ -70,16 +72,53 @@ pub struct StateData {
     pub circuits: Vec<Option<CircuitStateData>>,
     pub queue: Vec<UpdateTask>,
     pub updates: Vec<(usize, Option<Duration>)>,
+    #[serde(default)]
+    pub time: SimTime,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub events: Vec<(SimTime, UpdateTask)>,
+    #[serde(default)]
+    pub clock: ClockMode,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default)]
+    pub name: String,
+    /// Seed of the unordered update queue
+    #[serde(default = "SeededState::random_seed")]
+    pub seed: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CircuitBoardData {
+    /// Format version, boards saved before versioning are 0
+    #[serde(default)]
+    pub version: u32,
+
     pub wires: Vec<Option<WireData>>,
     pub circuits: Vec<Option<CircuitData>>,
     pub states: Vec<Option<StateData>>,
 
     #[serde(default)]
-    pub ordered: bool
+    pub ordered: bool,
+
+    #[serde(default)]
+    pub compiled: bool,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl CircuitBoardData {
+    pub const VERSION: u32 = 1;
+
+    /// Type that circuits saved as `ty` by older versions load as
+    pub fn migrate_circuit_type(version: u32, ty: &DynStaticStr) -> DynStaticStr {
+        match (version, ty.deref()) {
+            // Version 0 pullups pulled wires low
+            (0, "pullup") => "pulldown".into(),
+            _ => ty.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1,21 +1,32 @@
 use std::{
     any::{Any, TypeId},
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::{self, Write},
+    ops::Deref,
+    sync::{
+        atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    thread::ThreadId,
     time::Duration,
 };
 
 #[cfg(not(feature = "single_thread"))]
 use std::thread::{self, JoinHandle};
 
-use crate::{time::Instant, containers::Queue};
+use crate::{time::Instant, containers::{Queue, SeededState}};
 use eframe::epaint::Color32;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    analyzer::LogicAnalyzer,
+    breakpoint::{Breakpoint, BreakpointHit, BreakpointTarget, HitSource},
     board::CircuitBoard,
     circuits::*,
+    compiled::CompiledRegions,
     containers::FixedVec,
-    unwrap_option_or_break, unwrap_option_or_return,
+    unwrap_option_or_break, unwrap_option_or_continue, unwrap_option_or_return,
+    vector::Vec2i,
     wires::*,
     Mutex, RwLock,
 };
@@ -25,6 +36,378 @@ pub enum UpdateTask {
     CircuitSignals { id: usize, pin: Option<usize> },
     WireState { id: usize, skip_state_ckeck: bool },
     PinInput { circuit: usize, id: usize },
+    PinOutput {
+        circuit: usize,
+        id: usize,
+        state: WireState,
+        /// Direction pin switches to along with its state
+        #[serde(default)]
+        dir: Option<PinDirection>,
+    },
+    /// Timed update, see [`CircuitImpl::update`]
+    CircuitUpdate { id: usize },
+}
+
+/// Simulation time in nanoseconds, propagation delays use the same units
+pub type SimTime = u64;
+
+pub fn to_sim_time(duration: Duration) -> SimTime {
+    duration.as_nanos().min(SimTime::MAX as u128) as SimTime
+}
+
+pub fn to_duration(time: SimTime) -> Duration {
+    Duration::from_nanos(time)
+}
+
+/// How simulation time relates to wall time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ClockMode {
+    /// Jump straight to the next scheduled update
+    AsFastAsPossible,
+    /// One simulated second takes one real second
+    #[default]
+    RealTime,
+    /// Simulation time runs this many times faster than wall time
+    Scaled(f32),
+}
+
+impl ClockMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockMode::AsFastAsPossible => "As fast as possible",
+            ClockMode::RealTime => "Real time",
+            ClockMode::Scaled(_) => "Scaled",
+        }
+    }
+}
+
+/// Updates per second every state is limited to, 0 if unlimited
+static UPDATE_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+/// Work done by a state, see [`State::stats`]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SimulationStats {
+    /// Tasks run since state creation
+    pub tasks: u64,
+    /// Time spent running them
+    pub busy: Duration,
+}
+
+/// Spreads tasks over time to keep under [`State::update_limit`]
+#[derive(Default)]
+struct RateLimiter {
+    budget: f64,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Longest time unused budget is kept for, so idle states don't run a burst of tasks
+    const MAX_SAVED_SECS: f64 = 0.05;
+
+    /// Number of tasks that can run now
+    fn available(&mut self, limit: u64) -> usize {
+        let now = Instant::now();
+        let elapsed = self
+            .last
+            .and_then(|last| now.checked_duration_since(last))
+            .unwrap_or_default();
+        self.last = Some(now);
+
+        let limit = limit as f64;
+        let max = (limit * Self::MAX_SAVED_SECS).max(1.0);
+        self.budget = (self.budget + elapsed.as_secs_f64() * limit).min(max);
+        self.budget as usize
+    }
+
+    fn consume(&mut self, tasks: usize) {
+        self.budget -= tasks as f64;
+    }
+
+    /// When at least one task can run
+    fn next_available(&self, limit: u64) -> Instant {
+        let missing = (1.0 - self.budget).max(0.0);
+        Instant::now() + Duration::from_secs_f64(missing / limit as f64)
+    }
+}
+
+/// Maps wall time onto simulation time
+#[derive(Default)]
+struct SimClock {
+    mode: ClockMode,
+
+    /// Wall time and simulation time clock was synchronized at
+    anchor: Option<(Instant, SimTime)>,
+}
+
+impl SimClock {
+    fn scale(&self) -> Option<f64> {
+        match self.mode {
+            ClockMode::AsFastAsPossible => None,
+            ClockMode::RealTime => Some(1.0),
+            ClockMode::Scaled(scale) => Some(scale.max(f32::EPSILON) as f64),
+        }
+    }
+
+    /// Simulation time that should be reached by now, None if there's no limit
+    fn target(&mut self, now: SimTime) -> Option<SimTime> {
+        let scale = self.scale()?;
+        let (wall, sim) = *self.anchor.get_or_insert_with(|| (Instant::now(), now));
+        let elapsed = Instant::now()
+            .checked_duration_since(wall)
+            .unwrap_or_default()
+            .as_nanos() as f64;
+        Some(sim + (elapsed * scale) as SimTime)
+    }
+
+    /// Wall time at which simulation should reach `time`
+    fn wall_time(&self, time: SimTime) -> Instant {
+        match (self.scale(), self.anchor) {
+            (Some(scale), Some((wall, sim))) => {
+                let nanos = time.saturating_sub(sim) as f64 / scale;
+                wall + Duration::from_nanos(nanos.min(SimTime::MAX as f64) as SimTime)
+            }
+            _ => Instant::now(),
+        }
+    }
+
+    /// Stop advancing time until next [`SimClock::target`] call
+    fn stop(&mut self) {
+        self.anchor = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationStep {
+    /// Single update task
+    Task,
+    /// Until update queue is empty, without advancing simulation time
+    Settle,
+    /// Advance simulation time by given amount
+    Ticks(SimTime),
+}
+
+/// Time-ordered buckets of delayed updates.
+/// Updates scheduled for the same time run in the order they were scheduled
+#[derive(Default)]
+struct EventWheel {
+    now: SimTime,
+    buckets: BTreeMap<SimTime, VecDeque<UpdateTask>>,
+
+    /// Last scheduled state, last scheduled direction
+    /// and number of pending outputs for each pin
+    pending_outputs: HashMap<(usize, usize), (WireState, Option<PinDirection>, usize)>,
+}
+
+impl EventWheel {
+    fn load(now: SimTime, events: &[(SimTime, UpdateTask)]) -> Self {
+        let mut wheel = Self {
+            now,
+            ..Default::default()
+        };
+        for (time, task) in events {
+            wheel.schedule(time.saturating_sub(now), *task);
+        }
+        wheel
+    }
+
+    fn schedule(&mut self, delay: SimTime, task: UpdateTask) {
+        if let UpdateTask::PinOutput {
+            circuit,
+            id,
+            state,
+            dir,
+        } = task
+        {
+            let pending = self.pending_outputs.entry((circuit, id)).or_default();
+            pending.0 = state;
+            pending.1 = dir.or(pending.1);
+            pending.2 += 1;
+        }
+        self.buckets.entry(self.now + delay).or_default().push_back(task);
+    }
+
+    fn projected_output(
+        &self,
+        circuit: usize,
+        id: usize,
+    ) -> Option<(WireState, Option<PinDirection>)> {
+        self.pending_outputs.get(&(circuit, id)).map(|p| (p.0, p.1))
+    }
+
+    /// Advances time to the nearest scheduled update and returns it.
+    /// Won't go past `until`, if specified
+    fn pop(&mut self, until: Option<SimTime>) -> Option<UpdateTask> {
+        let mut bucket = self.buckets.first_entry()?;
+        if until.is_some_and(|until| *bucket.key() > until) {
+            return None;
+        }
+        self.now = *bucket.key();
+
+        let task = bucket.get_mut().pop_front();
+        if bucket.get().is_empty() {
+            bucket.remove();
+        }
+
+        if let Some(UpdateTask::PinOutput { circuit, id, .. }) = task {
+            if let Some(pending) = self.pending_outputs.get_mut(&(circuit, id)) {
+                pending.2 -= 1;
+                if pending.2 == 0 {
+                    self.pending_outputs.remove(&(circuit, id));
+                }
+            }
+        }
+        task
+    }
+
+    fn save(&self) -> Vec<(SimTime, UpdateTask)> {
+        self.buckets
+            .iter()
+            .flat_map(|(time, tasks)| tasks.iter().map(|t| (*time, *t)))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.now = 0;
+        self.buckets.clear();
+        self.pending_outputs.clear();
+    }
+}
+
+/// Counts wire changes and circuit updates within a settle window,
+/// which ends every time update queue runs empty
+#[derive(Default)]
+struct OscillationTracker {
+    wire_changes: HashMap<usize, usize>,
+    circuit_updates: HashMap<usize, usize>,
+
+    wires: HashSet<usize>,
+    circuits: HashSet<usize>,
+}
+
+impl OscillationTracker {
+    /// Returns true if wire went over the budget for the first time
+    fn wire_changed(&mut self, id: usize, budget: usize) -> bool {
+        let count = self.wire_changes.entry(id).or_default();
+        *count += 1;
+        *count > budget && self.wires.insert(id)
+    }
+
+    /// Returns true if circuit went over the budget for the first time
+    fn circuit_updated(&mut self, id: usize, budget: usize) -> bool {
+        let count = self.circuit_updates.entry(id).or_default();
+        *count += 1;
+        *count > budget && self.circuits.insert(id)
+    }
+
+    fn end_window(&mut self) {
+        self.wire_changes.clear();
+        self.circuit_updates.clear();
+    }
+
+    fn clear(&mut self) {
+        self.end_window();
+        self.wires.clear();
+        self.circuits.clear();
+    }
+}
+
+/// Work caused by each circuit and wire while profiling is enabled, since state creation
+/// or last [`State::clear_profile`], see [`State::profile`] and [`State::set_profiling`]
+#[derive(Default, Clone, Debug)]
+pub struct ActivityProfile {
+    /// `update_signals` calls per circuit
+    pub circuit_updates: HashMap<usize, u64>,
+    /// State changes per wire
+    pub wire_changes: HashMap<usize, u64>,
+}
+
+impl ActivityProfile {
+    pub fn max_circuit_updates(&self) -> u64 {
+        self.circuit_updates.values().copied().max().unwrap_or(0)
+    }
+
+    pub fn max_wire_changes(&self) -> u64 {
+        self.wire_changes.values().copied().max().unwrap_or(0)
+    }
+
+    /// How busy item with `count` is compared to the busiest one with `max`, from 0 to 1.
+    /// Logarithmic, so items with moderate activity are still visible
+    pub fn heat(count: u64, max: u64) -> f32 {
+        if count == 0 || max == 0 {
+            return 0.0;
+        }
+        ((count as f32).ln_1p() / (max as f32).ln_1p()).min(1.0)
+    }
+}
+
+/// Compiled evaluation in progress, see [`State::run_compiled`]
+struct CompiledRun {
+    regions: Arc<CompiledRegions>,
+    /// Wire updates requested during evaluation, run as part of it
+    wires: Vec<UpdateTask>,
+}
+
+/// Compiled circuits waiting for update during [`State::run_compiled`]
+struct CompiledSweep<'a> {
+    regions: &'a CompiledRegions,
+    /// Ordered by rank, so every circuit runs after all circuits driving it
+    dirty: BTreeSet<(u32, usize)>,
+}
+
+impl CompiledSweep<'_> {
+    fn is_compiled(&self, circuit: usize) -> bool {
+        self.regions.rank(circuit).is_some()
+    }
+
+    fn mark(&mut self, circuit: usize) {
+        if let Some(rank) = self.regions.rank(circuit) {
+            self.dirty.insert((rank, circuit));
+        }
+    }
+}
+
+/// Periodic snapshots of simulation state, oldest first
+struct History {
+    snapshots: VecDeque<(SimTime, Arc<crate::io::StateData>)>,
+    interval: SimTime,
+
+    /// Time next snapshot will be taken at
+    next: SimTime,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            interval: State::DEFAULT_HISTORY_INTERVAL,
+            next: 0,
+        }
+    }
+}
+
+/// Problem found while simulating
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Diagnostic {
+    /// Wire connects pins of different bit widths
+    WidthMismatch { wire: usize },
+    /// Wire changed state more times than allowed without settling
+    OscillatingWire { wire: usize },
+    /// Circuit updated more times than allowed without settling
+    OscillatingCircuit { circuit: usize },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::WidthMismatch { wire } => {
+                write!(f, "Wire {wire} connects pins of different widths")
+            }
+            Diagnostic::OscillatingWire { wire } => write!(f, "Wire {wire} doesn't settle"),
+            Diagnostic::OscillatingCircuit { circuit } => {
+                write!(f, "Circuit {circuit} doesn't settle")
+            }
+        }
+    }
 }
 
 #[derive(Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
@@ -34,14 +417,158 @@ pub enum WireState {
     True,
     False,
     Error,
+    Bus(BusState),
+}
+
+/// Value of a multi-bit wire.
+/// Bit is None if it isn't in `defined` and not set in `value`,
+/// Error if it isn't in `defined`, but set in `value`
+#[derive(Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct BusState {
+    width: u8,
+    defined: u32,
+    value: u32,
+}
+
+impl BusState {
+    pub fn width(self) -> u32 {
+        self.width as u32
+    }
+
+    /// Width comes from saved data, so it isn't trusted to be in 1..=32
+    fn mask(self) -> u32 {
+        u32::MAX
+            .checked_shr(32u32.saturating_sub(self.width as u32))
+            .unwrap_or(0)
+    }
+
+    /// True if any bit is [`WireState::Error`]
+    pub fn has_error(self) -> bool {
+        !self.defined & self.value & self.mask() != 0
+    }
+
+    fn bit(self, bit: u32) -> WireState {
+        let defined = (self.defined >> bit) & 1 != 0;
+        let value = (self.value >> bit) & 1 != 0;
+        match (defined, value) {
+            (true, value) => value.into(),
+            (false, false) => WireState::None,
+            (false, true) => WireState::Error,
+        }
+    }
+
+    fn combine(self, other: BusState) -> BusState {
+        let error = (!self.defined & self.value)
+            | (!other.defined & other.value)
+            | (self.defined & other.defined & (self.value ^ other.value));
+        let defined = (self.defined | other.defined) & !error;
+        let driven = (self.defined & self.value) | (other.defined & other.value);
+        let value = (defined & driven) | error;
+
+        BusState {
+            width: self.width,
+            defined: defined & self.mask(),
+            value: value & self.mask(),
+        }
+    }
 }
 
 impl WireState {
+    pub const MAX_WIDTH: u32 = 32;
+
+    /// Returns None for width-agnostic [`WireState::None`]
+    pub fn width(self) -> Option<u32> {
+        match self {
+            WireState::None => None,
+            WireState::True | WireState::False | WireState::Error => Some(1),
+            WireState::Bus(bus) => Some(bus.width()),
+        }
+    }
+
+    /// Single-bit states occupy bit 0, except [`WireState::Error`], which poisons every bit
+    pub fn bit(self, bit: u32) -> WireState {
+        match self {
+            WireState::Bus(bus) if bit < bus.width() => bus.bit(bit),
+            WireState::Bus(_) => WireState::None,
+            WireState::Error => WireState::Error,
+            state if bit == 0 => state,
+            _ => WireState::None,
+        }
+    }
+
+    /// True for [`WireState::Error`] and buses with at least one error bit
+    pub fn has_error(self) -> bool {
+        match self {
+            WireState::Error => true,
+            WireState::Bus(bus) => bus.has_error(),
+            _ => false,
+        }
+    }
+
+    /// Builds a state from single-bit states, starting from bit 0.
+    /// Missing bits are None, all-None values become [`WireState::None`]
+    pub fn from_bits(width: u32, bits: impl IntoIterator<Item = WireState>) -> WireState {
+        let width = width.clamp(1, Self::MAX_WIDTH);
+        let mut bus = BusState {
+            width: width as u8,
+            defined: 0,
+            value: 0,
+        };
+        for (i, bit) in bits.into_iter().take(width as usize).enumerate() {
+            match bit {
+                WireState::None => {}
+                WireState::True => {
+                    bus.defined |= 1 << i;
+                    bus.value |= 1 << i;
+                }
+                WireState::False => bus.defined |= 1 << i,
+                WireState::Error | WireState::Bus(_) => bus.value |= 1 << i,
+            }
+        }
+
+        if bus.defined == 0 && bus.value == 0 {
+            WireState::None
+        } else if width == 1 {
+            bus.bit(0)
+        } else {
+            WireState::Bus(bus)
+        }
+    }
+
+    /// Applies `f` to every bit of a `width`-bit value
+    pub fn map_bits(self, width: u32, f: impl Fn(WireState) -> WireState) -> WireState {
+        Self::from_bits(width, (0..width).map(|i| f(self.bit(i))))
+    }
+
+    /// Fills bits no strong driver drives with weakly driven ones
+    pub fn resolve_weak(self, weak: WireState) -> WireState {
+        if weak == WireState::None {
+            return self;
+        }
+        let width = match (self.width(), weak.width()) {
+            (None, _) => return weak,
+            (Some(a), Some(b)) if a == b => a,
+            // Width mismatch is reported separately
+            _ => return self,
+        };
+        let bits = (0..width).map(|i| match self.bit(i) {
+            WireState::None => weak.bit(i),
+            bit => bit,
+        });
+        WireState::from_bits(width, bits)
+    }
+
     pub fn combine(self, state: WireState) -> WireState {
         match (self, state) {
             (WireState::None, other) | (other, WireState::None) => other,
             (WireState::Error, _) | (_, WireState::Error) => WireState::Error,
 
+            (WireState::Bus(a), WireState::Bus(b)) if a.width == b.width => {
+                WireState::Bus(a.combine(b))
+            }
+            // Width mismatch
+            (WireState::Bus(_), _) | (_, WireState::Bus(_)) => WireState::Error,
+
             (WireState::True, WireState::False) => WireState::Error,
             (WireState::False, WireState::True) => WireState::Error,
 
@@ -56,14 +583,42 @@ impl WireState {
             Self::True => [0, 255, 0],
             Self::False => [0, 127, 0],
             Self::Error => [200, 0, 0],
+            Self::Bus(bus) => {
+                if bus.defined & bus.value != bus.value {
+                    [200, 0, 0]
+                } else if bus.defined == 0 {
+                    [0, 0, 200]
+                } else {
+                    [0, 160, 160]
+                }
+            }
         };
         Color32::from_rgb(rgb[0], rgb[1], rgb[2])
     }
 
-    pub fn combine_boolean(self, state: WireState, combiner: impl FnOnce(bool, bool) -> bool) -> WireState {
+    pub fn combine_boolean(
+        self,
+        state: WireState,
+        combiner: impl Fn(bool, bool) -> bool,
+    ) -> WireState {
         match (self, state) {
+            (WireState::Bus(a), WireState::Bus(b)) if a.width == b.width => WireState::from_bits(
+                a.width(),
+                (0..a.width()).map(|i| Self::combine_bit_boolean(a.bit(i), b.bit(i), &combiner)),
+            ),
+            (a, b) => Self::combine_bit_boolean(a, b, &combiner),
+        }
+    }
+
+    fn combine_bit_boolean(
+        a: WireState,
+        b: WireState,
+        combiner: &dyn Fn(bool, bool) -> bool,
+    ) -> WireState {
+        match (a, b) {
             (WireState::None, other) | (other, WireState::None) => other,
             (WireState::Error, _) | (_, WireState::Error) => WireState::Error,
+            (WireState::Bus(_), _) | (_, WireState::Bus(_)) => WireState::Error,
 
             (WireState::True, WireState::False) => combiner(true, false).into(),
             (WireState::False, WireState::True) => combiner(false, true).into(),
@@ -74,6 +629,391 @@ impl WireState {
     }
 }
 
+/// Wire state that can be read without locking, see [`WireStates`]
+#[derive(Default)]
+struct AtomicWireState {
+    /// Odd while the state is being written
+    seq: AtomicU32,
+    /// Variant, and width shifted by 8 bits for buses
+    kind: AtomicU32,
+    /// Bus `defined` bits in upper half and `value` bits in lower one
+    bits: AtomicU64,
+}
+
+impl AtomicWireState {
+    fn encode(state: WireState) -> (u32, u64) {
+        match state {
+            WireState::None => (0, 0),
+            WireState::True => (1, 0),
+            WireState::False => (2, 0),
+            WireState::Error => (3, 0),
+            WireState::Bus(bus) => (
+                4 | ((bus.width as u32) << 8),
+                ((bus.defined as u64) << 32) | bus.value as u64,
+            ),
+        }
+    }
+
+    fn decode(kind: u32, bits: u64) -> WireState {
+        match kind & 0xff {
+            1 => WireState::True,
+            2 => WireState::False,
+            3 => WireState::Error,
+            4 => WireState::Bus(BusState {
+                width: (kind >> 8) as u8,
+                defined: (bits >> 32) as u32,
+                value: bits as u32,
+            }),
+            _ => WireState::None,
+        }
+    }
+
+    fn load(&self) -> WireState {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                let kind = self.kind.load(Ordering::Relaxed);
+                let bits = self.bits.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return Self::decode(kind, bits);
+                }
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Returns previous state
+    fn swap(&self, state: WireState) -> WireState {
+        let seq = loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                break seq;
+            }
+            std::hint::spin_loop();
+        };
+        fence(Ordering::Release);
+
+        let old = Self::decode(
+            self.kind.load(Ordering::Relaxed),
+            self.bits.load(Ordering::Relaxed),
+        );
+        let (kind, bits) = Self::encode(state);
+        self.kind.store(kind, Ordering::Relaxed);
+        self.bits.store(bits, Ordering::Relaxed);
+
+        self.seq.store(seq + 2, Ordering::Release);
+        old
+    }
+}
+
+/// Pin state and direction that can be read without locking, see [`PinStates`]
+#[derive(Default)]
+struct AtomicPinState {
+    state: AtomicWireState,
+    /// 0 while pin uses its default direction, variant index + 1 otherwise
+    dir: AtomicU8,
+}
+
+impl AtomicPinState {
+    const DIRS: [PinDirection; 4] = [
+        PinDirection::Inside,
+        PinDirection::Outside,
+        PinDirection::Weak,
+        PinDirection::Custom,
+    ];
+
+    fn encode_dir(dir: Option<PinDirection>) -> u8 {
+        dir.and_then(|dir| Self::DIRS.iter().position(|d| *d == dir))
+            .map_or(0, |i| i as u8 + 1)
+    }
+
+    fn decode_dir(dir: u8) -> Option<PinDirection> {
+        Self::DIRS.get((dir as usize).checked_sub(1)?).copied()
+    }
+
+    fn dir(&self) -> Option<PinDirection> {
+        Self::decode_dir(self.dir.load(Ordering::Acquire))
+    }
+
+    /// Returns previous direction
+    fn swap_dir(&self, dir: Option<PinDirection>) -> Option<PinDirection> {
+        Self::decode_dir(self.dir.swap(Self::encode_dir(dir), Ordering::AcqRel))
+    }
+}
+
+/// Lazily allocated array which never moves its elements, so they can be used without locking.
+/// Segment `i` holds `FIRST << i` elements
+struct Segments<T, const FIRST: usize> {
+    segments: [OnceLock<Box<[T]>>; SEGMENTS],
+    /// One past the highest id ever allocated
+    len: AtomicUsize,
+}
+
+const SEGMENTS: usize = 32;
+
+impl<T, const FIRST: usize> Default for Segments<T, FIRST> {
+    fn default() -> Self {
+        Self {
+            segments: Default::default(),
+            len: Default::default(),
+        }
+    }
+}
+
+impl<T: Default, const FIRST: usize> Segments<T, FIRST> {
+    /// Segment and index in it, None for ids past the last segment
+    fn locate(id: usize) -> Option<(usize, usize)> {
+        let n = id / FIRST + 1;
+        let segment = n.ilog2() as usize;
+        if segment >= SEGMENTS {
+            return None;
+        }
+        Some((segment, id - FIRST * ((1 << segment) - 1)))
+    }
+
+    fn get(&self, id: usize) -> Option<&T> {
+        let (segment, index) = Self::locate(id)?;
+        self.segments[segment].get().map(|s| &s[index])
+    }
+
+    /// Allocates element's segment if needed
+    fn get_or_alloc(&self, id: usize) -> Option<&T> {
+        let (segment, index) = Self::locate(id)?;
+        let segment = self.segments[segment].get_or_init(|| {
+            let len = FIRST << segment;
+            (0..len).map(|_| T::default()).collect()
+        });
+        self.len.fetch_max(id + 1, Ordering::Relaxed);
+        Some(&segment[index])
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+}
+
+/// Wire states indexed by wire id. Reading takes no locks,
+/// so painting never waits for simulation
+#[derive(Default)]
+pub struct WireStates {
+    states: Segments<AtomicWireState, 64>,
+    /// Shared by writers, held exclusively while clearing,
+    /// so no write lands in the middle of it
+    clearing: RwLock<()>,
+}
+
+impl WireStates {
+    pub fn get(&self, id: usize) -> WireState {
+        self.states.get(id).map(|s| s.load()).unwrap_or_default()
+    }
+
+    /// Returns previous state. Ids past the last segment can't hold a state and stay None
+    pub fn swap(&self, id: usize, state: WireState) -> WireState {
+        let _clearing = self.clearing.read();
+        self.swap_unlocked(id, state)
+    }
+
+    fn swap_unlocked(&self, id: usize, state: WireState) -> WireState {
+        let atomic = unwrap_option_or_return!(self.states.get_or_alloc(id), WireState::None);
+        atomic.swap(state)
+    }
+
+    pub fn clear(&self) {
+        let _clearing = self.clearing.write();
+        self.clear_unlocked();
+    }
+
+    fn clear_unlocked(&self) {
+        for id in 0..self.states.len() {
+            self.swap_unlocked(id, WireState::None);
+        }
+    }
+
+    /// Replaces every state with ones from `other`
+    fn copy_from(&self, other: &WireStates) {
+        let _clearing = self.clearing.write();
+        self.clear_unlocked();
+        for id in 0..other.states.len() {
+            self.swap_unlocked(id, other.get(id));
+        }
+    }
+
+    fn save(&self) -> Vec<Option<WireState>> {
+        (0..self.states.len())
+            .map(|id| Some(self.get(id)).filter(|s| *s != WireState::None))
+            .collect()
+    }
+
+    fn load(data: &[Option<WireState>]) -> Self {
+        let states = Self::default();
+        for (id, state) in data.iter().enumerate() {
+            if let Some(state) = state {
+                states.swap(id, *state);
+            }
+        }
+        states
+    }
+}
+
+/// Pin states and directions indexed by circuit and pin id.
+/// Like [`WireStates`], reading takes no locks
+#[derive(Default)]
+pub struct PinStates {
+    circuits: Segments<Segments<AtomicPinState, 4>, 64>,
+    /// Shared by writers, held exclusively while clearing,
+    /// so no write lands in the middle of it
+    clearing: RwLock<()>,
+}
+
+impl PinStates {
+    fn pin(&self, circuit: usize, pin: usize) -> Option<&AtomicPinState> {
+        self.circuits.get(circuit)?.get(pin)
+    }
+
+    fn pin_or_alloc(&self, circuit: usize, pin: usize) -> Option<&AtomicPinState> {
+        self.circuits.get_or_alloc(circuit)?.get_or_alloc(pin)
+    }
+
+    pub fn get(&self, circuit: usize, pin: usize) -> WireState {
+        self.pin(circuit, pin)
+            .map(|p| p.state.load())
+            .unwrap_or_default()
+    }
+
+    /// Direction set by the circuit, None if pin uses its default one
+    pub fn dir(&self, circuit: usize, pin: usize) -> Option<PinDirection> {
+        self.pin(circuit, pin)?.dir()
+    }
+
+    /// Returns previous state
+    pub fn swap(&self, circuit: usize, pin: usize, state: WireState) -> WireState {
+        let _clearing = self.clearing.read();
+        let atomic = unwrap_option_or_return!(self.pin_or_alloc(circuit, pin), WireState::None);
+        atomic.state.swap(state)
+    }
+
+    /// Returns previous direction
+    pub fn swap_dir(&self, circuit: usize, pin: usize, dir: PinDirection) -> Option<PinDirection> {
+        let _clearing = self.clearing.read();
+        self.pin_or_alloc(circuit, pin)?.swap_dir(Some(dir))
+    }
+
+    /// Forgets states and directions of circuit's pins
+    pub fn clear_circuit(&self, circuit: usize) {
+        let _clearing = self.clearing.read();
+        self.clear_circuit_unlocked(circuit);
+    }
+
+    fn clear_circuit_unlocked(&self, circuit: usize) {
+        let pins = unwrap_option_or_return!(self.circuits.get(circuit));
+        for id in 0..pins.len() {
+            if let Some(pin) = pins.get(id) {
+                pin.state.swap(WireState::None);
+                pin.swap_dir(None);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let _clearing = self.clearing.write();
+        self.clear_unlocked();
+    }
+
+    fn clear_unlocked(&self) {
+        for circuit in 0..self.circuits.len() {
+            self.clear_circuit_unlocked(circuit);
+        }
+    }
+
+    /// Replaces every state and direction with ones from `other`
+    fn copy_from(&self, other: &PinStates) {
+        let _clearing = self.clearing.write();
+        self.clear_unlocked();
+        for circuit in 0..other.circuits.len() {
+            let (pins, dirs) = other.save_circuit(circuit);
+            self.load_circuit(circuit, &pins, &dirs);
+        }
+    }
+
+    /// States and directions of circuit's pins, without trailing unset ones
+    fn save_circuit(&self, circuit: usize) -> (Vec<Option<WireState>>, Vec<Option<PinDirection>>) {
+        let len = self.circuits.get(circuit).map_or(0, |pins| pins.len());
+        let states = (0..len)
+            .map(|id| Some(self.get(circuit, id)).filter(|s| *s != WireState::None))
+            .collect();
+        let dirs = (0..len).map(|id| self.dir(circuit, id)).collect();
+        (trim_none(states), trim_none(dirs))
+    }
+
+    fn load_circuit(
+        &self,
+        circuit: usize,
+        states: &[Option<WireState>],
+        dirs: &[Option<PinDirection>],
+    ) {
+        for (id, state) in states.iter().enumerate() {
+            let state = unwrap_option_or_continue!(state);
+            let pin = unwrap_option_or_continue!(self.pin_or_alloc(circuit, id));
+            pin.state.swap(*state);
+        }
+        for (id, dir) in dirs.iter().enumerate() {
+            let dir = unwrap_option_or_continue!(dir);
+            let pin = unwrap_option_or_continue!(self.pin_or_alloc(circuit, id));
+            pin.swap_dir(Some(*dir));
+        }
+    }
+
+    fn load(data: &[Option<crate::io::CircuitStateData>]) -> Self {
+        let states = Self::default();
+        for (circuit, data) in data.iter().enumerate() {
+            if let Some(data) = data {
+                states.load_circuit(circuit, &data.pins, &data.pin_dirs);
+            }
+        }
+        states
+    }
+}
+
+fn trim_none<T>(mut vec: Vec<Option<T>>) -> Vec<Option<T>> {
+    while vec.last().is_some_and(Option::is_none) {
+        vec.pop();
+    }
+    vec
+}
+
+/// Pin that contributes to wire state, see [`State::wire_drivers`]
+pub struct WireDriver {
+    pub pin: CircuitPinId,
+    pub dir: PinDirection,
+    pub pos: Vec2i,
+    /// Value the pin drives, before resolving with other drivers
+    pub state: WireState,
+}
+
+/// Formats bits starting from the most significant one:
+/// `0` and `1` for defined bits, `z` for None and `x` for Error
+impl fmt::Display for WireState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.width().unwrap_or(1);
+        for bit in (0..width).rev() {
+            let c = match self.bit(bit) {
+                WireState::None => 'z',
+                WireState::True => '1',
+                WireState::False => '0',
+                WireState::Error | WireState::Bus(_) => 'x',
+            };
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<bool> for WireState {
     fn from(value: bool) -> Self {
         match value {
@@ -89,10 +1029,9 @@ pub trait InternalCircuitState: Any + Send + Sync {
     }
 }
 
+/// Internal state of a circuit, its pin states are kept in [`PinStates`]
 #[derive(Default)]
 pub struct CircuitState {
-    pub pins: FixedVec<WireState>,
-    pub pin_dirs: FixedVec<PinDirection>,
     pub internal: Option<Box<dyn InternalCircuitState>>,
 }
 impl CircuitState {
@@ -111,22 +1050,15 @@ impl CircuitState {
         unsafe { &mut *(b.as_mut() as *mut dyn InternalCircuitState as *mut T) }
     }
 
-    pub fn save(&self) -> crate::io::CircuitStateData {
-        crate::io::CircuitStateData {
-            pins: self.pins.inner().clone(),
-            pin_dirs: self.pin_dirs.inner().clone(),
-            internal: self
-                .internal
-                .as_ref()
-                .map(|s| s.serialize())
-                .unwrap_or_default(),
-        }
+    pub fn save(&self) -> serde_intermediate::Intermediate {
+        self.internal
+            .as_ref()
+            .map(|s| s.serialize())
+            .unwrap_or_default()
     }
 
     fn load(data: &crate::io::CircuitStateData, id: usize, board: &CircuitBoard) -> Self {
         Self {
-            pins: FixedVec::from_option_vec(data.pins.clone()),
-            pin_dirs: FixedVec::from_option_vec(data.pin_dirs.clone()),
             internal: match &data.internal {
                 serde_intermediate::Intermediate::Unit => None,
                 data => board
@@ -170,6 +1102,36 @@ impl StateCollection {
         (id, state)
     }
 
+    /// Creates a copy of existing state, which then runs independently
+    pub fn clone_state(&self, state: usize) -> Option<(usize, Arc<State>)> {
+        let source = self.get(state)?;
+        let mut data = source.save();
+        data.name = format!("{} copy", self.state_name(state).unwrap_or_default());
+        let clone = Arc::new(State::load(&data, source.board.clone()));
+
+        let mut vec = self.states.write();
+        let id = vec.first_free_pos();
+        vec.set(clone.clone(), id);
+        drop(vec);
+
+        // Starts the thread if source is running
+        clone.set_paused(source.is_paused());
+        Some((id, clone))
+    }
+
+    /// State name, or its id if it has none
+    pub fn state_name(&self, state: usize) -> Option<String> {
+        let name = self.get(state)?.name();
+        Some(match name.is_empty() {
+            true => format!("State {state}"),
+            false => name,
+        })
+    }
+
+    pub fn remove_state(&self, state: usize) -> Option<Arc<State>> {
+        self.states.write().remove(state)
+    }
+
     pub fn update_pin_input(&self, circuit_id: usize, id: usize) {
         for state in self.states.read().iter() {
             state.update_pin_input(circuit_id, id);
@@ -240,48 +1202,86 @@ impl StateCollection {
 
 #[derive(Clone)]
 pub struct State {
-    pub wires: Arc<RwLock<FixedVec<Arc<RwLock<WireState>>>>>,
+    pub wires: Arc<WireStates>,
+    pub pins: Arc<PinStates>,
     pub circuits: Arc<RwLock<FixedVec<Arc<RwLock<CircuitState>>>>>,
 
     queue: Arc<Mutex<Queue<UpdateTask>>>,
+    /// Seed of the unordered queue, see [`State::rerun`]
+    seed: Arc<AtomicU64>,
+    events: Arc<Mutex<EventWheel>>,
 
     #[cfg(not(feature = "single_thread"))]
     thread: Arc<RwLock<Option<StateThreadHandle>>>,
 
     board: Arc<RwLock<CircuitBoard>>,
-    circuit_updates_removes: Arc<Mutex<Vec<usize>>>,
+    name: Arc<Mutex<String>>,
+
+    /// Circuits and simulation time of their next timed update
+    pub updates: Arc<Mutex<Vec<(usize, SimTime)>>>,
+    clock: Arc<Mutex<SimClock>>,
+
+    paused: Arc<AtomicBool>,
+    width_mismatches: Arc<Mutex<HashSet<usize>>>,
+    oscillations: Arc<Mutex<OscillationTracker>>,
+    oscillation_budget: Arc<AtomicUsize>,
+
+    pub analyzer: Arc<Mutex<LogicAnalyzer>>,
+    history: Arc<Mutex<History>>,
+
+    breakpoints: Arc<RwLock<Vec<Breakpoint>>>,
+    breakpoint_hit: Arc<Mutex<Option<BreakpointHit>>>,
+    /// Task being run and the thread running it, to tell which changes it caused
+    running_task: Arc<Mutex<Option<(ThreadId, UpdateTask)>>>,
+
+    stats: Arc<Mutex<SimulationStats>>,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    profile: Arc<Mutex<ActivityProfile>>,
+    /// Checked before touching `profile`, so it costs nothing while nobody looks at it
+    profiling: Arc<AtomicBool>,
 
-    pub updates: Arc<Mutex<Vec<(usize, Instant)>>>,
+    compiled_run: Arc<Mutex<Option<CompiledRun>>>,
 }
 
 impl State {
     pub fn new(board: Arc<RwLock<CircuitBoard>>) -> Self {
-        let ordered = board.read().is_ordered_queue();
+        let (ordered, breakpoints) = {
+            let board = board.read();
+            (board.is_ordered_queue(), board.breakpoints.clone())
+        };
+        let seed = SeededState::random_seed();
         Self {
             wires: Default::default(),
+            pins: Default::default(),
             circuits: Default::default(),
-            queue: Arc::new(Mutex::new(Queue::new(vec![], ordered))),
+            queue: Arc::new(Mutex::new(Queue::new(vec![], ordered, seed))),
+            seed: Arc::new(AtomicU64::new(seed)),
+            events: Default::default(),
             #[cfg(not(feature = "single_thread"))]
             thread: Default::default(),
             board,
-            circuit_updates_removes: Default::default(),
+            name: Default::default(),
             updates: Default::default(),
+            clock: Default::default(),
+            paused: Default::default(),
+            width_mismatches: Default::default(),
+            oscillations: Default::default(),
+            oscillation_budget: Arc::new(AtomicUsize::new(Self::DEFAULT_OSCILLATION_BUDGET)),
+            analyzer: Default::default(),
+            history: Default::default(),
+            breakpoints,
+            breakpoint_hit: Default::default(),
+            running_task: Default::default(),
+            stats: Default::default(),
+            rate_limiter: Default::default(),
+            profile: Default::default(),
+            profiling: Default::default(),
+            compiled_run: Default::default(),
         }
     }
 
     pub fn read_wire(&self, id: usize) -> WireState {
-        self.wires
-            .read()
-            .get(id)
-            .map(|s| *s.read())
-            .unwrap_or_default()
-    }
-
-    pub fn get_wire(&self, id: usize) -> Arc<RwLock<WireState>> {
-        self.wires
-            .write()
-            .get_or_create_mut(id, Default::default)
-            .clone()
+        self.wires.get(id)
     }
 
     pub fn read_circuit(&self, id: usize) -> Option<Arc<RwLock<CircuitState>>> {
@@ -296,16 +1296,16 @@ impl State {
     }
 
     pub fn set_circuit_update_interval(&self, id: usize, dur: Option<Duration>) {
+        let now = self.sim_time();
         let mut updates = self.updates.lock();
         match dur {
             Some(dur) => {
+                // Zero interval would make simulation time stuck
+                let time = now + to_sim_time(dur).max(1);
                 let index = updates.iter_mut().find(|v| v.0 == id);
                 match index {
-                    Some(v) => v.1 = Instant::now() + dur,
-                    None => {
-                        let _i = updates.len();
-                        updates.push((id, Instant::now() + dur))
-                    }
+                    Some(v) => v.1 = time,
+                    None => updates.push((id, time)),
                 }
             }
             None => {
@@ -325,40 +1325,48 @@ impl State {
     }
 
     pub fn save(&self) -> crate::io::StateData {
-        let now = Instant::now();
+        let now = self.sim_time();
         crate::io::StateData {
-            wires: self
-                .wires
-                .read()
-                .inner()
-                .iter()
-                .map(|ws| ws.as_ref().map(|ws| *ws.read()))
-                .collect(),
-            circuits: self
-                .circuits
-                .read()
-                .inner()
-                .iter()
-                .map(|cs| cs.as_ref().map(|cs| cs.read().save()))
-                .collect(),
+            wires: self.wires.save(),
+            circuits: self.save_circuits(),
             queue: self.queue.lock().iter().copied().collect(),
+            time: now,
+            events: self.events.lock().save(),
             updates: self
                 .updates
                 .lock()
                 .iter()
-                .map(|(id, time)| (*id, time.checked_duration_since(now)))
+                .map(|(id, time)| (*id, time.checked_sub(now).map(to_duration)))
                 .collect(),
+            clock: self.clock_mode(),
+            name: self.name(),
+            seed: self.seed(),
         }
     }
 
-    pub fn load(data: &crate::io::StateData, board: Arc<RwLock<CircuitBoard>>) -> State {
-        let now = Instant::now();
-
-        let wires = data
-            .wires
-            .iter()
-            .map(|w| w.map(|w| Arc::new(RwLock::new(w))))
+    /// Pin and internal states of every circuit that has any
+    fn save_circuits(&self) -> Vec<Option<crate::io::CircuitStateData>> {
+        let circuits = self.circuits.read();
+        let len = circuits.inner().len().max(self.pins.circuits.len());
+        let data = (0..len)
+            .map(|id| {
+                let internal = circuits.get(id).map(|cs| cs.read().save());
+                let (pins, pin_dirs) = self.pins.save_circuit(id);
+                if internal.is_none() && pins.is_empty() && pin_dirs.is_empty() {
+                    return None;
+                }
+                Some(crate::io::CircuitStateData {
+                    pins,
+                    pin_dirs,
+                    internal: internal.unwrap_or_default(),
+                })
+            })
             .collect();
+        trim_none(data)
+    }
+
+    pub fn load(data: &crate::io::StateData, board: Arc<RwLock<CircuitBoard>>) -> State {
+        let now = data.time;
 
         let board_ref = board.read();
         let circuits = data
@@ -375,143 +1383,611 @@ impl State {
         let updates = data
             .updates
             .iter()
-            .map(|(id, dur)| (*id, dur.map(|d| now + d).unwrap_or(now)))
+            .map(|(id, dur)| (*id, now + dur.map(to_sim_time).unwrap_or(0)))
             .collect();
 
-        let ordered = board.read().is_ordered_queue();
+        let (ordered, breakpoints) = {
+            let board = board.read();
+            (board.is_ordered_queue(), board.breakpoints.clone())
+        };
         Self {
-            wires: Arc::new(RwLock::new(FixedVec::from_option_vec(wires))),
+            wires: Arc::new(WireStates::load(&data.wires)),
+            pins: Arc::new(PinStates::load(&data.circuits)),
             circuits: Arc::new(RwLock::new(FixedVec::from_option_vec(circuits))),
-            queue: Arc::new(Mutex::new(Queue::new(data.queue.clone(), ordered))),
+            queue: Arc::new(Mutex::new(Queue::new(data.queue.clone(), ordered, data.seed))),
+            seed: Arc::new(AtomicU64::new(data.seed)),
+            events: Arc::new(Mutex::new(EventWheel::load(data.time, &data.events))),
             #[cfg(not(feature = "single_thread"))]
             thread: Arc::new(RwLock::new(None)),
             board,
-            circuit_updates_removes: Default::default(),
+            name: Arc::new(Mutex::new(data.name.clone())),
             updates: Arc::new(Mutex::new(updates)),
+            clock: Arc::new(Mutex::new(SimClock {
+                mode: data.clock,
+                anchor: None,
+            })),
+            paused: Default::default(),
+            width_mismatches: Default::default(),
+            oscillations: Default::default(),
+            oscillation_budget: Arc::new(AtomicUsize::new(Self::DEFAULT_OSCILLATION_BUDGET)),
+            analyzer: Default::default(),
+            history: Default::default(),
+            breakpoints,
+            breakpoint_hit: Default::default(),
+            running_task: Default::default(),
+            stats: Default::default(),
+            rate_limiter: Default::default(),
+            profile: Default::default(),
+            profiling: Default::default(),
+            compiled_run: Default::default(),
         }
     }
 
     pub fn update_wire(&self, wire: usize, skip_state_ckeck: bool) {
-        self.schedule_update(UpdateTask::WireState {
+        let task = UpdateTask::WireState {
             id: wire,
             skip_state_ckeck,
-        });
+        };
+        if let Some(run) = &mut *self.compiled_run.lock() {
+            run.wires.push(task);
+            return;
+        }
+        self.schedule_update(task);
+    }
+
+    /// Whether compiled evaluation is running and `wire` is inside one of its regions
+    pub fn is_compiled_wire(&self, wire: usize) -> bool {
+        self.compiled_run
+            .lock()
+            .as_ref()
+            .is_some_and(|run| run.regions.is_internal_wire(wire))
+    }
+
+    pub fn update_circuit_signals(&self, circuit: usize, pin: Option<usize>) {
+        self.schedule_update(UpdateTask::CircuitSignals { id: circuit, pin });
+    }
+
+    pub fn update_pin_input(&self, circuit: usize, id: usize) {
+        self.schedule_update(UpdateTask::PinInput { circuit, id });
+    }
+
+    /// Schedules output pin state change `delay` units from now,
+    /// optionally switching pin direction at the same time.
+    /// Skipped if pin would already end up in this state.
+    /// `current` direction is None if pin direction was never set
+    pub fn schedule_pin_output(
+        &self,
+        pin: CircuitPinId,
+        current: (WireState, Option<PinDirection>),
+        state: WireState,
+        dir: Option<PinDirection>,
+        delay: SimTime,
+    ) {
+        let mut events = self.events.lock();
+        let (projected_state, projected_dir) =
+            match events.projected_output(pin.circuit_id, pin.id) {
+                Some((state, dir)) => (state, dir.or(current.1)),
+                None => current,
+            };
+        if projected_state == state && dir.is_none_or(|dir| projected_dir == Some(dir)) {
+            return;
+        }
+        events.schedule(
+            delay,
+            UpdateTask::PinOutput {
+                circuit: pin.circuit_id,
+                id: pin.id,
+                state,
+                dir,
+            },
+        );
+        drop(events);
+
+        #[cfg(not(feature = "single_thread"))]
+        self.poke_thread(true, false);
+    }
+
+    pub fn sim_time(&self) -> SimTime {
+        self.events.lock().now
+    }
+
+    pub fn clock_mode(&self) -> ClockMode {
+        self.clock.lock().mode
+    }
+
+    pub fn set_clock_mode(&self, mode: ClockMode) {
+        let mut clock = self.clock.lock();
+        clock.mode = mode;
+        clock.stop();
+        drop(clock);
+
+        #[cfg(not(feature = "single_thread"))]
+        self.poke_thread(true, false);
+    }
+
+    pub fn reset_wire(&self, wire: usize) {
+        self.wires.swap(wire, WireState::None);
+        self.width_mismatches.lock().remove(&wire);
+        self.oscillations.lock().wires.remove(&wire);
+    }
+
+    pub fn reset_circuit(&self, circuit: usize) {
+        self.circuits.write().remove(circuit);
+        self.pins.clear_circuit(circuit);
+        self.oscillations.lock().circuits.remove(&circuit);
+        self.set_circuit_update_interval(circuit, None);
+    }
+
+    pub fn set_ordered(&self, ordered: bool) {
+        self.queue.lock().set_ordered(ordered, self.seed());
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed.load(Ordering::Relaxed)
+    }
+
+    /// Restarts unordered queue from `seed`.
+    /// Updates only repeat exactly when started from a reset state, see [`State::rerun`]
+    pub fn set_seed(&self, seed: u64) {
+        let mut queue = self.queue.lock();
+        self.seed.store(seed, Ordering::Relaxed);
+        queue.set_seed(seed);
+    }
+
+    /// Resets simulation and runs it again from the start with a fixed queue seed,
+    /// so races in unordered mode play out the same way every time
+    pub fn rerun(&self, seed: u64) {
+        self.set_seed(seed);
+        self.reset();
+        self.update_everything();
+    }
+
+    fn schedule_update(&self, task: UpdateTask) {
+        let mut queue = self.queue.lock();
+        queue.enqueue(task);
+
+        #[cfg(not(feature = "single_thread"))]
+        self.poke_thread(true, false);
+    }
+
+    #[cfg(feature = "single_thread")]
+    pub fn update(&self) {
+        if !self.is_paused() {
+            self.update_once(5000);
+        }
+    }
+
+    pub fn update_limit() -> u64 {
+        UPDATE_LIMIT.load(Ordering::Relaxed)
+    }
+
+    /// Limits updates per second of every state, 0 removes the limit
+    pub fn set_update_limit(limit: u64) {
+        UPDATE_LIMIT.store(limit, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> SimulationStats {
+        *self.stats.lock()
+    }
+
+    pub fn profile(&self) -> ActivityProfile {
+        self.profile.lock().clone()
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiling.load(Ordering::Relaxed)
+    }
+
+    pub fn set_profiling(&self, profiling: bool) {
+        self.profiling.store(profiling, Ordering::Relaxed);
+    }
+
+    pub fn clear_profile(&self) {
+        *self.profile.lock() = Default::default();
+    }
+
+    pub fn name(&self) -> String {
+        self.name.lock().clone()
+    }
+
+    pub fn set_name(&self, name: String) {
+        *self.name.lock() = name;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        self.clock.lock().stop();
+        if !paused {
+            *self.breakpoint_hit.lock() = None;
+        }
+
+        #[cfg(not(feature = "single_thread"))]
+        if !paused {
+            self.poke_thread(true, false);
+        }
+    }
+
+    /// Default number of changes a wire or circuit can go through before settling
+    pub const DEFAULT_OSCILLATION_BUDGET: usize = 1000;
+
+    pub fn oscillation_budget(&self) -> usize {
+        self.oscillation_budget.load(Ordering::Relaxed)
     }
 
-    pub fn update_circuit_signals(&self, circuit: usize, pin: Option<usize>) {
-        self.schedule_update(UpdateTask::CircuitSignals { id: circuit, pin });
+    pub fn set_oscillation_budget(&self, budget: usize) {
+        self.oscillation_budget.store(budget, Ordering::Relaxed);
     }
 
-    pub fn update_pin_input(&self, circuit: usize, id: usize) {
-        self.schedule_update(UpdateTask::PinInput { circuit, id });
+    /// Number of snapshots kept in history
+    pub const HISTORY_LEN: usize = 256;
+    pub const DEFAULT_HISTORY_INTERVAL: SimTime = 10_000_000;
+
+    pub fn history_interval(&self) -> SimTime {
+        self.history.lock().interval
     }
 
-    pub fn reset_wire(&self, wire: usize) {
-        self.wires.write().remove(wire);
+    pub fn set_history_interval(&self, interval: SimTime) {
+        let now = self.sim_time();
+        let mut history = self.history.lock();
+        history.next = history.next.min(now + interval);
+        history.interval = interval;
     }
 
-    pub fn reset_circuit(&self, circuit: usize) {
-        self.circuits.write().remove(circuit);
-        self.set_circuit_update_interval(circuit, None);
+    /// Times of snapshots in history, oldest first
+    pub fn history_times(&self) -> Vec<SimTime> {
+        let history = self.history.lock();
+        history.snapshots.iter().map(|(time, _)| *time).collect()
     }
 
-    pub fn set_ordered(&self, ordered: bool) {
-        self.queue.lock().set_ordered(ordered);
+    /// Takes a snapshot if it's time to. Snapshots after it are discarded,
+    /// since simulation went a different way after rewinding
+    fn record_history(&self) {
+        let now = self.sim_time();
+        if now < self.history.lock().next {
+            return;
+        }
+
+        let data = Arc::new(self.save());
+        let mut history = self.history.lock();
+        while history.snapshots.back().is_some_and(|(time, _)| *time >= now) {
+            history.snapshots.pop_back();
+        }
+        if history.snapshots.len() >= Self::HISTORY_LEN {
+            history.snapshots.pop_front();
+        }
+        history.snapshots.push_back((now, data));
+        history.next = now + history.interval.max(1);
     }
 
-    fn schedule_update(&self, task: UpdateTask) {
-        let mut queue = self.queue.lock();
-        queue.enqueue(task);
+    /// Pauses simulation and restores snapshot from history
+    pub fn rewind(&self, index: usize) {
+        let data = {
+            let history = self.history.lock();
+            unwrap_option_or_return!(history.snapshots.get(index)).1.clone()
+        };
+        self.set_paused(true);
+        self.restore(&data);
 
-        #[cfg(not(feature = "single_thread"))]
-        self.poke_thread(true, false);
+        let mut history = self.history.lock();
+        history.next = data.time + history.interval.max(1);
     }
 
-    #[cfg(feature = "single_thread")]
-    pub fn update(&self) {
-        self.update_once(5000);
+    /// Replaces simulation state with saved one in place,
+    /// so everything holding this state sees the change
+    pub fn restore(&self, data: &crate::io::StateData) {
+        let loaded = State::load(data, self.board.clone());
+
+        let sim_lock = { self.board.read().sim_lock.clone() };
+        let sim_lock = sim_lock.write();
+
+        std::mem::swap(&mut *self.queue.lock(), &mut *loaded.queue.lock());
+        self.seed.store(data.seed, Ordering::Relaxed);
+        self.wires.copy_from(&loaded.wires);
+        self.pins.copy_from(&loaded.pins);
+        std::mem::swap(&mut *self.circuits.write(), &mut *loaded.circuits.write());
+        std::mem::swap(&mut *self.events.lock(), &mut *loaded.events.lock());
+        std::mem::swap(&mut *self.updates.lock(), &mut *loaded.updates.lock());
+
+        self.width_mismatches.lock().clear();
+        self.oscillations.lock().clear();
+        self.clock.lock().stop();
+        self.analyzer.lock().truncate(data.time);
+
+        drop(sim_lock);
     }
 
-    fn update_once(&self, queue_limit: usize) -> Option<Instant> {
-        // Lock shared simulation, so placing/deleting won't interrupt anything
+    /// Maximum number of tasks one [`State::step`] can run,
+    /// so non-settling circuits won't hang the caller
+    const STEP_TASK_LIMIT: usize = 100_000;
+
+    /// Runs simulation on the calling thread, meant to be used while paused.
+    /// Returns last executed task and whether step finished
+    /// before hitting the task limit or a breakpoint
+    pub fn step(&self, step: SimulationStep) -> (Option<UpdateTask>, bool) {
         let sim_lock = { self.board.read().sim_lock.clone() };
         let sim_lock = sim_lock.read();
-        let mut circuit_updates_removes = self.circuit_updates_removes.lock();
-
-        let nearest_update = {
-            let mut updates = self.updates.lock();
-            let mut nearest_update = None;
-            let now = Instant::now();
-            circuit_updates_removes.clear();
-            for (i, upd) in updates.iter_mut().enumerate() {
-                if upd.1 <= now {
-                    let board = self.board.read();
-                    let circ = board.circuits.get(upd.0);
-                    if let Some(circ) = circ {
-                        let imp = circ.imp.read();
-
-                        let state = CircuitStateContext::new(self, circ);
-                        imp.update(&state);
-                        match imp.update_interval(&state) {
-                            Some(d) => {
-                                upd.1 = now + d;
-                            }
-                            None => circuit_updates_removes.push(i),
-                        }
-                    } else {
-                        circuit_updates_removes.push(i);
-                    }
-                }
-                let closer = match nearest_update {
-                    Some(nu) => upd.1 < nu,
-                    None => true,
-                };
-                if closer {
-                    nearest_update = Some(upd.1);
+        *self.breakpoint_hit.lock() = None;
+
+        let (limit, until) = match step {
+            SimulationStep::Task => (1, None),
+            SimulationStep::Settle => (Self::STEP_TASK_LIMIT, Some(self.sim_time())),
+            SimulationStep::Ticks(ticks) => {
+                (Self::STEP_TASK_LIMIT, Some(self.sim_time() + ticks))
+            }
+        };
+
+        let start = Instant::now();
+        let mut tasks = 0;
+        let mut last_task = None;
+        let mut finished = false;
+        for _ in 0..limit {
+            let board = self.board.read();
+            let task = match self.next_task(until) {
+                Some(task) => task,
+                None => {
+                    finished = true;
+                    break;
                 }
+            };
+            self.run_task(&board, task);
+            tasks += 1;
+            last_task = Some(task);
+            if self.breakpoint_triggered() {
+                break;
             }
-            // `circuit_updates_removes` is ordered, removing in reverse order is safe
-            for i in circuit_updates_removes.drain(..).rev() {
-                updates.remove(i);
+        }
+        {
+            let mut stats = self.stats.lock();
+            stats.tasks += tasks;
+            stats.busy += Instant::now() - start;
+        }
+
+        if let (SimulationStep::Ticks(_), true, Some(until)) = (step, finished, until) {
+            let mut events = self.events.lock();
+            events.now = events.now.max(until);
+        }
+        self.record_history();
+
+        drop(sim_lock);
+        (last_task, finished || matches!(step, SimulationStep::Task))
+    }
+
+    /// Runs up to `queue_limit` tasks, respecting [`State::update_limit`].
+    /// Returns when it should be called next, None if there's nothing to do
+    fn update_once(&self, queue_limit: usize) -> Option<Instant> {
+        let limit = Self::update_limit();
+        let queue_limit = match limit {
+            0 => queue_limit,
+            limit => {
+                let mut limiter = self.rate_limiter.lock();
+                let available = limiter.available(limit);
+                if available == 0 {
+                    return Some(limiter.next_available(limit));
+                }
+                queue_limit.min(available)
             }
-            nearest_update
         };
 
+        let start = Instant::now();
+        let (tasks, next) = self.run_updates(queue_limit);
+        {
+            let mut stats = self.stats.lock();
+            stats.tasks += tasks as u64;
+            stats.busy += Instant::now() - start;
+        }
+
+        if limit > 0 {
+            let mut limiter = self.rate_limiter.lock();
+            limiter.consume(tasks);
+            if tasks >= queue_limit {
+                return Some(limiter.next_available(limit));
+            }
+        }
+        next
+    }
+
+    /// Returns number of tasks run and when to run more
+    fn run_updates(&self, queue_limit: usize) -> (usize, Option<Instant>) {
+        // Lock shared simulation, so placing/deleting won't interrupt anything
+        let sim_lock = { self.board.read().sim_lock.clone() };
+        let sim_lock = sim_lock.read();
+
+        let until = self.clock.lock().target(self.sim_time());
         let mut queue_counter = 0;
 
-        while !nearest_update.is_some_and(|nu| nu <= Instant::now()) && queue_counter < queue_limit
-        {
+        while queue_counter < queue_limit {
             let board = self.board.read();
-            let deq = { self.queue.lock().dequeue() };
-            let task = unwrap_option_or_break!(deq);
+            let task = unwrap_option_or_break!(self.next_task(until));
 
-            match task {
-                UpdateTask::WireState {
-                    id,
-                    skip_state_ckeck,
-                } => {
-                    if let Some(wire) = board.wires.get(id) {
-                        self.update_wire_now(wire, skip_state_ckeck);
+            self.run_task(&board, task);
+            queue_counter += 1;
+            if self.breakpoint_triggered() {
+                // Paused, so time mustn't advance past the task
+                self.record_history();
+                return (queue_counter, None);
+            }
+        }
+        self.record_history();
+        drop(sim_lock);
+
+        if queue_counter >= queue_limit {
+            return (queue_counter, Some(Instant::now()));
+        }
+
+        let next = {
+            let mut events = self.events.lock();
+            if let Some(until) = until {
+                events.now = events.now.max(until);
+            }
+            let next_event = events.buckets.first_key_value().map(|(time, _)| *time);
+            drop(events);
+
+            let next_update = self.updates.lock().iter().map(|(_, time)| *time).min();
+            next_event.into_iter().chain(next_update).min()
+        };
+
+        let mut clock = self.clock.lock();
+        let next = match next {
+            Some(next) => Some(clock.wall_time(next)),
+            None => {
+                // Nothing is scheduled, don't let time pass while idle
+                clock.stop();
+                None
+            }
+        };
+        (queue_counter, next)
+    }
+
+    /// Takes next task from the update queue. When queue is empty, advances simulation time
+    /// to the nearest delayed update or timed circuit update, unless it's past `until`
+    fn next_task(&self, until: Option<SimTime>) -> Option<UpdateTask> {
+        let task = { self.queue.lock().dequeue() };
+        if task.is_some() {
+            return task;
+        }
+
+        // Queue settled, move on to the next delayed update
+        self.oscillations.lock().end_window();
+
+        let next_update = {
+            let updates = self.updates.lock();
+            updates.iter().min_by_key(|(_, time)| *time).copied()
+        };
+        let mut events = self.events.lock();
+        let next_event = events.buckets.first_key_value().map(|(time, _)| *time);
+
+        match next_update {
+            Some((id, time))
+                if next_event.is_none_or(|event| time < event)
+                    && until.is_none_or(|until| time <= until) =>
+            {
+                events.now = events.now.max(time);
+                Some(UpdateTask::CircuitUpdate { id })
+            }
+            _ => events.pop(until),
+        }
+    }
+
+    fn run_task(&self, board: &CircuitBoard, task: UpdateTask) {
+        *self.running_task.lock() = Some((std::thread::current().id(), task));
+        self.run_task_inner(board, task);
+        *self.running_task.lock() = None;
+    }
+
+    fn run_task_inner(&self, board: &CircuitBoard, task: UpdateTask) {
+        match task {
+            UpdateTask::WireState {
+                id,
+                skip_state_ckeck,
+            } => {
+                if let Some(wire) = board.wires.get(id) {
+                    match board.compiled_regions() {
+                        Some(regions) => self.run_compiled(board, regions, |sweep| {
+                            self.update_wire_now(wire, skip_state_ckeck, Some(sweep))
+                        }),
+                        None => self.update_wire_now(wire, skip_state_ckeck, None),
                     }
                 }
-                UpdateTask::CircuitSignals { id, pin } => {
-                    if let Some(circuit) = board.circuits.get(id) {
-                        self.update_circuit_signals_now(circuit, pin);
+            }
+            UpdateTask::CircuitSignals { id, pin } => {
+                if let Some(circuit) = board.circuits.get(id) {
+                    // Compiled circuits are only updated in sweeps, so their delay stays consistent
+                    match board.compiled_regions().filter(|r| r.rank(id).is_some()) {
+                        Some(regions) => self.run_compiled(board, regions, |sweep| sweep.mark(id)),
+                        None => self.update_circuit_signals_now(circuit, pin),
                     }
                 }
-                UpdateTask::PinInput { circuit, id } => {
-                    if let Some(circuit) = board.circuits.get(circuit) {
-                        self.update_pin_input_now(circuit, id);
-                    }
+            }
+            UpdateTask::PinInput { circuit, id } => {
+                if let Some(circuit) = board.circuits.get(circuit) {
+                    self.update_pin_input_now(circuit, id);
                 }
             }
-            queue_counter += 1;
+            UpdateTask::PinOutput {
+                circuit,
+                id,
+                state,
+                dir,
+            } => {
+                if let Some(circuit) = board.circuits.get(circuit) {
+                    self.update_pin_output_now(circuit, id, state, dir);
+                }
+            }
+            UpdateTask::CircuitUpdate { id } => {
+                let interval = board.circuits.get(id).and_then(|circuit| {
+                    let imp = circuit.imp.read();
+                    let state_ctx = CircuitStateContext::new(self, circuit);
+                    imp.update(&state_ctx);
+                    imp.update_interval(&state_ctx)
+                });
+                self.set_circuit_update_interval(id, interval);
+            }
         }
-        drop(sim_lock);
-        match nearest_update {
-            Some(t) => Some(t),
-            None if queue_counter >= queue_limit => Some(Instant::now()),
-            _ => None,
+    }
+
+    /// Updates every compiled circuit affected by `start` in rank order,
+    /// so each of them is updated once and its output wires right after.
+    /// Outputs inside a region change immediately, delay applies where they leave it
+    fn run_compiled(
+        &self,
+        board: &CircuitBoard,
+        regions: Arc<CompiledRegions>,
+        start: impl FnOnce(&mut CompiledSweep),
+    ) {
+        // Swapped under queue lock, so wire updates requested by other threads
+        // end up either in the run or in the queue, never lost in between
+        {
+            let _queue = self.queue.lock();
+            *self.compiled_run.lock() = Some(CompiledRun {
+                regions: regions.clone(),
+                wires: vec![],
+            });
+        }
+
+        let mut sweep = CompiledSweep {
+            regions: &regions,
+            dirty: BTreeSet::new(),
+        };
+        start(&mut sweep);
+
+        let mut wires = vec![];
+        while self.breakpoint_hit.lock().is_none() {
+            let (_, id) = unwrap_option_or_break!(sweep.dirty.pop_first());
+            if let Some(circuit) = board.circuits.get(id) {
+                self.update_circuit_signals_now(circuit, None);
+            }
+
+            if let Some(run) = &mut *self.compiled_run.lock() {
+                wires.append(&mut run.wires);
+            }
+            for task in wires.drain(..) {
+                let UpdateTask::WireState {
+                    id,
+                    skip_state_ckeck,
+                } = task
+                else {
+                    continue;
+                };
+                if let Some(wire) = board.wires.get(id) {
+                    self.update_wire_now(wire, skip_state_ckeck, Some(&mut sweep));
+                }
+            }
+        }
+        {
+            let mut queue = self.queue.lock();
+            let run = self.compiled_run.lock().take();
+            for task in run.into_iter().flat_map(|run| run.wires) {
+                queue.enqueue(task);
+            }
+        }
+
+        // Stopped at a breakpoint, leave the rest to the queue
+        for (_, id) in sweep.dirty {
+            self.update_circuit_signals(id, None);
         }
     }
 
@@ -520,20 +1996,36 @@ impl State {
         circuit.imp.read().init_state(&state_ctx);
     }
 
-    fn update_wire_now(&self, wire: &Wire, skip_state_ckeck: bool) {
+    /// With `sweep`, compiled circuits reading the wire are added to it instead of being queued
+    fn update_wire_now(
+        &self,
+        wire: &Wire,
+        skip_state_ckeck: bool,
+        mut sweep: Option<&mut CompiledSweep>,
+    ) {
         let mut state = WireState::None;
+        let mut weak = WireState::None;
         let mut delayed_pins = vec![];
+        let mut width = None;
+        let mut width_mismatch = false;
         for (_, point) in wire.points.iter() {
             if let Some(pin_arc) = &point.pin {
                 let pin = pin_arc.read();
 
+                match width {
+                    None => width = Some(pin.width()),
+                    Some(w) => width_mismatch |= w != pin.width(),
+                }
+
                 match pin.direction(self) {
                     PinDirection::Inside => {}
                     PinDirection::Outside => state = state.combine(pin.get_state(self)),
+                    PinDirection::Weak => weak = weak.combine(pin.get_state(self)),
                     PinDirection::Custom => delayed_pins.push(pin_arc),
                 }
             }
         }
+        state = state.resolve_weak(weak);
 
         for pin in delayed_pins {
             let pin = pin.read();
@@ -549,19 +2041,46 @@ impl State {
             }
         }
 
-        let current = self.get_wire(wire.id);
-        if !skip_state_ckeck && *current.read() == state {
+        {
+            let mut width_mismatches = self.width_mismatches.lock();
+            if width_mismatch {
+                state = WireState::Error;
+                width_mismatches.insert(wire.id);
+            } else {
+                width_mismatches.remove(&wire.id);
+            }
+        }
+
+        if !skip_state_ckeck && self.wires.get(wire.id) == state {
             return;
         }
 
-        *current.write() = state;
+        let old = self.wires.swap(wire.id, state);
+        let time = self.sim_time();
+        self.analyzer.lock().record(wire.id, time, state);
+        if self.is_profiling() {
+            *self.profile.lock().wire_changes.entry(wire.id).or_default() += 1;
+        }
+        self.check_breakpoints(old, state, |t| *t == BreakpointTarget::Wire(wire.id));
+
+        let budget = self.oscillation_budget();
+        self.oscillations.lock().wire_changed(wire.id, budget);
+
         for (_, point) in wire.points.iter() {
             if let Some(pin) = &point.pin {
                 let pin = pin.read();
 
                 match pin.direction(self) {
-                    PinDirection::Inside => pin.set_input(self, state, true),
-                    PinDirection::Outside => {}
+                    PinDirection::Inside => match sweep.as_deref_mut() {
+                        Some(sweep) if sweep.is_compiled(pin.id.circuit_id) => {
+                            if pin.get_state(self) != state {
+                                pin.set_input(self, state, false);
+                                sweep.mark(pin.id.circuit_id);
+                            }
+                        }
+                        _ => pin.set_input(self, state, true),
+                    },
+                    PinDirection::Outside | PinDirection::Weak => {}
                     PinDirection::Custom => pin.set_input(self, state, true),
                 }
             }
@@ -569,6 +2088,12 @@ impl State {
     }
 
     fn update_circuit_signals_now(&self, circuit: &Circuit, pin: Option<usize>) {
+        let budget = self.oscillation_budget();
+        self.oscillations.lock().circuit_updated(circuit.id, budget);
+        if self.is_profiling() {
+            *self.profile.lock().circuit_updates.entry(circuit.id).or_default() += 1;
+        }
+
         circuit
             .imp
             .read()
@@ -588,7 +2113,7 @@ impl State {
         match pin.direction(self) {
             PinDirection::Inside => {}
             PinDirection::Custom => {}
-            PinDirection::Outside => return,
+            PinDirection::Outside | PinDirection::Weak => return,
         }
 
         drop(info);
@@ -605,11 +2130,43 @@ impl State {
         }
 
         pin.set_input(self, new_state, false);
+        self.pin_changed(circuit.id, &pin.name(), old_state, new_state);
         drop(pin);
 
         self.update_circuit_signals_now(circuit, Some(id));
     }
 
+    fn update_pin_output_now(
+        &self,
+        circuit: &Circuit,
+        id: usize,
+        state: WireState,
+        dir: Option<PinDirection>,
+    ) {
+        let info = circuit.info.read();
+        let pin_info = unwrap_option_or_return!(info.pins.get(id));
+        let pin = pin_info.pin.read();
+
+        // Kept only if it changed
+        let dir = dir.filter(|&dir| self.pins.swap_dir(circuit.id, id, dir) != Some(dir));
+        let old = self.pins.swap(circuit.id, id, state);
+
+        if old != state {
+            self.pin_changed(circuit.id, &pin.name(), old, state);
+        } else if dir.is_none() {
+            return;
+        }
+
+        match pin.wire {
+            Some(wire) => self.update_wire(wire, dir.is_some()),
+            // Released pin reads nothing instead of its last input
+            None if dir == Some(PinDirection::Inside) => {
+                pin.set_input(self, Default::default(), true)
+            }
+            None => {}
+        }
+    }
+
     #[cfg(not(feature = "single_thread"))]
     fn poke_thread(&self, notify: bool, termination_req: bool) {
         let thread_sync = {
@@ -648,14 +2205,36 @@ impl State {
     }
 
     pub fn reset(&self) {
-        // Important to lock everything, so thread won't do anything
+        // Important to lock everything, so thread won't do anything,
+        // including finishing a task it's running
+        let sim_lock = { self.board.read().sim_lock.clone() };
+        let _sim_lock = sim_lock.write();
         let mut queue = self.queue.lock();
         let mut circuits = self.circuits.write();
-        let mut wires = self.wires.write();
 
         queue.clear();
         circuits.clear();
-        wires.clear();
+        self.wires.clear();
+        self.pins.clear();
+        self.events.lock().clear();
+        self.clock.lock().stop();
+        self.width_mismatches.lock().clear();
+        self.oscillations.lock().clear();
+        self.analyzer.lock().clear();
+        self.clear_profile();
+
+        let mut history = self.history.lock();
+        history.snapshots.clear();
+        history.next = 0;
+    }
+
+    /// Initializes all circuits like when they're placed and schedules update of everything
+    pub fn init(&self) {
+        let board = self.board.clone();
+        for circuit in board.read().circuits.iter() {
+            self.init_circuit(circuit);
+        }
+        self.update_everything();
     }
 
     pub fn update_everything(&self) {
@@ -682,6 +2261,127 @@ impl State {
     pub fn queue_len(&self) -> usize {
         self.queue.lock().len()
     }
+
+    /// Nothing is left to update, now or later
+    pub fn is_settled(&self) -> bool {
+        self.queue.lock().len() == 0
+            && self.events.lock().buckets.is_empty()
+            && self.updates.lock().is_empty()
+    }
+
+    /// Wires and circuits with problems, sorted by kind and id
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<_> = self
+            .width_mismatches
+            .lock()
+            .iter()
+            .map(|&wire| Diagnostic::WidthMismatch { wire })
+            .collect();
+        {
+            let oscillations = self.oscillations.lock();
+            diagnostics.extend(
+                oscillations
+                    .wires
+                    .iter()
+                    .map(|&wire| Diagnostic::OscillatingWire { wire }),
+            );
+            diagnostics.extend(
+                oscillations
+                    .circuits
+                    .iter()
+                    .map(|&circuit| Diagnostic::OscillatingCircuit { circuit }),
+            );
+        }
+        diagnostics.sort_unstable();
+        diagnostics
+    }
+
+    /// Pins which drive or mutate given wire, sorted by position
+    pub fn wire_drivers(&self, wire: &Wire) -> Vec<WireDriver> {
+        let mut drivers: Vec<_> = wire
+            .points
+            .iter()
+            .filter_map(|(pos, point)| {
+                let pin = point.pin.as_ref()?.read();
+                let dir = pin.direction(self);
+                (dir != PinDirection::Inside).then(|| WireDriver {
+                    pin: pin.id,
+                    dir,
+                    pos: *pos,
+                    state: pin.get_state(self),
+                })
+            })
+            .collect();
+        drivers.sort_unstable_by_key(|d| (d.pos.y(), d.pos.x()));
+        drivers
+    }
+
+    /// Breakpoint that paused simulation, cleared when it's resumed or stepped
+    pub fn breakpoint_hit(&self) -> Option<BreakpointHit> {
+        self.breakpoint_hit.lock().clone()
+    }
+
+    /// Checks breakpoints on a circuit pin, called when its value is committed
+    pub(crate) fn pin_changed(&self, circuit: usize, pin: &str, old: WireState, new: WireState) {
+        self.check_breakpoints(old, new, |t| match t {
+            BreakpointTarget::Pin { circuit: c, pin: p } => *c == circuit && p.deref() == pin,
+            BreakpointTarget::Wire(_) => false,
+        });
+    }
+
+    /// Pauses simulation if an enabled breakpoint on `target` matches the change.
+    /// Only the first hit is kept. Changes made by other threads while a task runs,
+    /// like user input, aren't attributed to it
+    fn check_breakpoints(
+        &self,
+        old: WireState,
+        new: WireState,
+        target: impl Fn(&BreakpointTarget) -> bool,
+    ) {
+        if old == new {
+            return;
+        }
+        let breakpoint = {
+            let breakpoints = self.breakpoints.read();
+            let breakpoint = breakpoints
+                .iter()
+                .find(|b| b.enabled && target(&b.target) && b.condition.matches(old, new));
+            unwrap_option_or_return!(breakpoint).clone()
+        };
+
+        let source = match *self.running_task.lock() {
+            Some((thread, task)) if thread == std::thread::current().id() => HitSource::Task(task),
+            _ => HitSource::External,
+        };
+
+        let mut hit = self.breakpoint_hit.lock();
+        if hit.is_some() {
+            return;
+        }
+        *hit = Some(BreakpointHit {
+            breakpoint,
+            source,
+            time: self.sim_time(),
+            old,
+            new,
+        });
+        drop(hit);
+        self.set_paused(true);
+    }
+
+    /// Whether a breakpoint was hit since simulation was last resumed or stepped
+    fn breakpoint_triggered(&self) -> bool {
+        self.breakpoint_hit.lock().is_some()
+    }
+
+    pub fn oscillating_wires(&self) -> HashSet<usize> {
+        self.oscillations.lock().wires.clone()
+    }
+
+    /// Forgets detected oscillations, they'll be reported again if still present
+    pub fn clear_oscillations(&self) {
+        self.oscillations.lock().clear();
+    }
 }
 
 impl Drop for State {
@@ -717,7 +2417,10 @@ impl StateThread {
                 }
             }
 
-            let wait = self.state.update_once(200);
+            let wait = match self.state.is_paused() {
+                true => None,
+                false => self.state.update_once(200),
+            };
 
             match wait {
                 Some(nu) => {
@@ -748,7 +2451,87 @@ impl StateThread {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
+    use std::{ops::Deref, sync::Arc};
+
+    use super::{SimulationStep, State, WireState};
+    use crate::{
+        board::CircuitBoard,
+        circuits::{builtin_preview_map, CircuitStateContext},
+        BasicLoadingContext, RwLock,
+    };
+
+    /// Loads board saved as RON, with every builtin circuit available
+    pub(crate) fn load_board(data: &str) -> Arc<RwLock<CircuitBoard>> {
+        let previews = builtin_preview_map();
+        let data = ron::from_str(data).unwrap();
+        CircuitBoard::load(
+            &data,
+            &BasicLoadingContext {
+                previews: &previews,
+            },
+        )
+    }
+
+    /// Creates a paused state, simulated on the calling thread, and settles it
+    pub(crate) fn create_state(board: &Arc<RwLock<CircuitBoard>>) -> Arc<State> {
+        let state = init_state(board);
+        settle(&state);
+        state
+    }
+
+    /// Creates a paused state, simulated on the calling thread, with everything scheduled
+    /// for update
+    pub(crate) fn init_state(board: &Arc<RwLock<CircuitBoard>>) -> Arc<State> {
+        let board_ref = board.read();
+        let (_, state) = board_ref.states.create_state(board.clone());
+        state.set_paused(true);
+        for circuit in board_ref.circuits.iter() {
+            board_ref.states.init_circuit(circuit);
+        }
+        state.update_everything();
+        state
+    }
+
+    pub(crate) fn settle(state: &State) {
+        for _ in 0..1000 {
+            if state.is_settled() {
+                return;
+            }
+            state.step(SimulationStep::Ticks(10));
+        }
+        panic!("state didn't settle");
+    }
+
+    /// Sets output pin of a circuit, like user input would, and settles the state
+    pub(crate) fn drive(
+        board: &Arc<RwLock<CircuitBoard>>,
+        state: &State,
+        circuit: usize,
+        value: WireState,
+    ) {
+        {
+            let board = board.read();
+            let circuit = board.circuits.get(circuit).unwrap();
+            let info = circuit.info.read();
+            let pin = info.pins.first().unwrap();
+            pin.set_state(&CircuitStateContext::new(state, circuit), value);
+        }
+        settle(state);
+    }
+
+    pub(crate) fn pin_state(
+        board: &Arc<RwLock<CircuitBoard>>,
+        state: &State,
+        circuit: usize,
+        pin: &str,
+    ) -> WireState {
+        let board = board.read();
+        let circuit = board.circuits.get(circuit).unwrap();
+        let info = circuit.info.read();
+        let pin = info.pins.iter().find(|p| p.name.deref() == pin).unwrap();
+        pin.get_state(&CircuitStateContext::new(state, circuit))
+    }
 
     fn sync_send<T: Sync + Send>() {}
 
@@ -756,4 +2539,260 @@ mod test {
     fn sync_send_state() {
         sync_send::<super::State>();
     }
+
+    #[test]
+    fn bus_combine() {
+        use super::WireState;
+
+        let a = WireState::from_bits(4, [WireState::True, WireState::None, WireState::False]);
+        let b = WireState::from_bits(4, [WireState::True, WireState::False, WireState::True]);
+        let combined = a.combine(b);
+
+        assert_eq!(combined.width(), Some(4));
+        assert_eq!(combined.bit(0), WireState::True);
+        assert_eq!(combined.bit(1), WireState::False);
+        assert_eq!(combined.bit(2), WireState::Error);
+        assert_eq!(combined.bit(3), WireState::None);
+
+        assert_eq!(a.combine(WireState::True), WireState::Error);
+        assert_eq!(a.combine(WireState::from_bits(8, [WireState::True])), WireState::Error);
+        assert_eq!(WireState::from_bits(1, [WireState::False]), WireState::False);
+    }
+
+    #[test]
+    fn bus_mask() {
+        use super::BusState;
+
+        let bus = |width| BusState {
+            width,
+            defined: 0,
+            value: 0,
+        };
+        assert_eq!(bus(0).mask(), 0);
+        assert_eq!(bus(4).mask(), 0xf);
+        assert_eq!(bus(32).mask(), u32::MAX);
+        assert_eq!(bus(40).mask(), u32::MAX);
+    }
+
+    #[test]
+    fn has_error() {
+        use super::WireState;
+
+        let bits = [WireState::True, WireState::Error, WireState::None];
+        assert!(WireState::from_bits(3, bits).has_error());
+        let bits = [WireState::True, WireState::False, WireState::None];
+        assert!(!WireState::from_bits(3, bits).has_error());
+        assert!(WireState::Error.has_error());
+        assert!(!WireState::True.has_error());
+        assert!(!WireState::None.has_error());
+    }
+
+    #[test]
+    fn weak_drive() {
+        use super::WireState;
+
+        assert_eq!(WireState::False.resolve_weak(WireState::True), WireState::False);
+        assert_eq!(WireState::None.resolve_weak(WireState::True), WireState::True);
+        let weak = WireState::True.combine(WireState::False);
+        assert_eq!(WireState::None.resolve_weak(weak), WireState::Error);
+
+        let strong = WireState::from_bits(4, [WireState::None, WireState::False]);
+        let weak = WireState::from_bits(4, [WireState::True; 4]);
+        let resolved = strong.resolve_weak(weak);
+        assert_eq!(resolved.bit(0), WireState::True);
+        assert_eq!(resolved.bit(1), WireState::False);
+        assert_eq!(resolved.bit(3), WireState::True);
+    }
+
+    #[test]
+    fn wire_states() {
+        use super::{Segments, WireState, WireStates};
+        use std::sync::Arc;
+
+        type WireSegments = Segments<(), 64>;
+        assert_eq!(WireSegments::locate(63), Some((0, 63)));
+        assert_eq!(WireSegments::locate(64), Some((1, 0)));
+        assert_eq!(WireSegments::locate(192), Some((2, 0)));
+        assert_eq!(WireSegments::locate(usize::MAX), None);
+
+        let bus = WireState::from_bits(32, [WireState::True, WireState::Error, WireState::False]);
+        let states = WireStates::default();
+        assert_eq!(states.get(1000), WireState::None);
+        assert_eq!(states.swap(1000, bus), WireState::None);
+        assert_eq!(states.swap(3, WireState::Error), WireState::None);
+        assert_eq!(states.get(1000), bus);
+        assert_eq!(states.swap(usize::MAX, bus), WireState::None);
+        assert_eq!(states.get(usize::MAX), WireState::None);
+        assert_eq!(states.save().len(), 1001);
+        assert_eq!(WireStates::load(&states.save()).get(3), WireState::Error);
+
+        states.clear();
+        assert_eq!(states.get(1000), WireState::None);
+
+        // Readers must never see a half-written state
+        let states = Arc::new(states);
+        let other = WireState::from_bits(4, [WireState::None, WireState::True]);
+        let writer = {
+            let states = states.clone();
+            std::thread::spawn(move || {
+                for i in 0..10000 {
+                    states.swap(5, if i % 2 == 0 { bus } else { other });
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let state = states.get(5);
+            assert!(state == bus || state == other || state == WireState::None);
+        }
+    }
+
+    #[test]
+    fn pin_states() {
+        use super::{PinDirection, PinStates, WireState};
+
+        let states = PinStates::default();
+        assert_eq!(states.get(200, 10), WireState::None);
+        assert_eq!(states.swap(200, 10, WireState::True), WireState::None);
+        assert_eq!(states.swap(200, 10, WireState::False), WireState::True);
+        assert_eq!(states.dir(200, 10), None);
+        assert_eq!(states.swap_dir(200, 10, PinDirection::Inside), None);
+        assert_eq!(
+            states.swap_dir(200, 10, PinDirection::Weak),
+            Some(PinDirection::Inside)
+        );
+        states.swap(3, 0, WireState::Error);
+
+        let (pins, dirs) = states.save_circuit(200);
+        assert_eq!(pins.len(), 11);
+        assert_eq!(dirs.len(), 11);
+        assert_eq!(states.save_circuit(100), (vec![], vec![]));
+
+        let copy = PinStates::default();
+        copy.swap(4, 0, WireState::True);
+        copy.copy_from(&states);
+        assert_eq!(copy.get(4, 0), WireState::None);
+        assert_eq!(copy.get(3, 0), WireState::Error);
+        assert_eq!(copy.get(200, 10), WireState::False);
+        assert_eq!(copy.dir(200, 10), Some(PinDirection::Weak));
+
+        states.clear_circuit(200);
+        assert_eq!(states.get(200, 10), WireState::None);
+        assert_eq!(states.dir(200, 10), None);
+        assert_eq!(states.get(3, 0), WireState::Error);
+        states.clear();
+        assert_eq!(states.get(3, 0), WireState::None);
+    }
+
+    /// Board with a `ty` circuit as circuit 0 and buttons driving each of `inputs`,
+    /// as circuits from 1 on
+    pub(crate) fn driven_board(
+        ty: &str,
+        props: &str,
+        inputs: &[&str],
+    ) -> Arc<RwLock<CircuitBoard>> {
+        let mut wires = String::new();
+        let mut pin_wires = String::new();
+        let mut buttons = String::new();
+        for (i, input) in inputs.iter().enumerate() {
+            let y = i * 2;
+            let button = i + 1;
+            wires += &format!(
+                r#"Some((points: [
+                    ([2, {y}], (pin: Some((name: "out", circuit: {button})))),
+                    ([5, {y}], (left: true, pin: Some((name: "{input}", circuit: 0)))),
+                ])),"#
+            );
+            pin_wires += &format!(r#"("{input}", {i}),"#);
+            buttons += &format!(
+                r#"Some((ty: "button", pos: [0, {y}], pin_wires: [("out", {i})], props: ({{}}))),"#
+            );
+        }
+        let circuit = format!(
+            r#"Some((ty: "{ty}", pos: [5, 0], pin_wires: [{pin_wires}], props: ({{{props}}})))"#
+        );
+        load_board(&format!(
+            "(wires: [{wires}], circuits: [{circuit}, {buttons}], states: [])"
+        ))
+    }
+
+    #[test]
+    fn activity_heat() {
+        use super::ActivityProfile;
+
+        assert_eq!(ActivityProfile::heat(0, 100), 0.0);
+        assert_eq!(ActivityProfile::heat(5, 0), 0.0);
+        assert_eq!(ActivityProfile::heat(100, 100), 1.0);
+        let low = ActivityProfile::heat(10, 1000);
+        assert!(low > 0.3 && low < 0.5);
+    }
+
+    #[test]
+    fn oscillation_budget() {
+        let mut tracker = super::OscillationTracker::default();
+
+        for _ in 0..3 {
+            assert!(!tracker.wire_changed(0, 3));
+        }
+        tracker.end_window();
+        for _ in 0..3 {
+            assert!(!tracker.wire_changed(0, 3));
+        }
+        assert!(tracker.wire_changed(0, 3));
+        assert!(!tracker.wire_changed(0, 3));
+        assert!(tracker.wires.contains(&0));
+
+        tracker.end_window();
+        assert!(tracker.wires.contains(&0));
+        tracker.clear();
+        assert!(tracker.wires.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "single_thread"))]
+    fn oscillating_ring() {
+        use super::Diagnostic;
+
+        // Zero-delay not gate driving its own input, pullup gets it started
+        let board = load_board(
+            r#"(
+            version: 1,
+            wires: [
+                Some((points: [
+                    ([1, 0], (pin: Some((name: "out", circuit: 0)))),
+                    ([2, 0], (left: true)),
+                    ([2, 1], (up: true)),
+                    ([0, 1], (right: true)),
+                    ([0, 0], (down: true, pin: Some((name: "in", circuit: 0)))),
+                    ([3, 0], (left: true, pin: Some((name: "pin", circuit: 1)))),
+                ])),
+            ],
+            circuits: [
+                Some((ty: "not", pos: [0, 0], pin_wires: [("in", 0), ("out", 0)],
+                    props: ({"delay": 0}))),
+                Some((ty: "pullup", pos: [3, 0], pin_wires: [("pin", 0)], props: ({}))),
+            ],
+            states: [],
+        )"#,
+        );
+        // Simulated by its own thread, like in the app
+        let (_, state) = board.read().states.create_state(board.clone());
+        state.init();
+        let start = std::time::Instant::now();
+        while state.diagnostics().len() < 2 {
+            assert!(start.elapsed().as_secs() < 5, "oscillation wasn't detected");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        // Oscillation is only reported, ring oscillators are allowed to keep running
+        assert!(!state.is_paused());
+        state.set_paused(true);
+
+        assert!(state.oscillating_wires().contains(&0));
+        assert_eq!(
+            state.diagnostics(),
+            [
+                Diagnostic::OscillatingWire { wire: 0 },
+                Diagnostic::OscillatingCircuit { circuit: 0 }
+            ]
+        );
+    }
 }
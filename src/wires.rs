@@ -1,4 +1,9 @@
-use std::{collections::HashMap, num::NonZeroU32, sync::Arc};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::BuildHasherDefault,
+    num::NonZeroU32,
+    sync::Arc,
+};
 
 use eframe::epaint::Color32;
 
@@ -17,10 +22,14 @@ pub struct FoundWirePoint {
     pub pos: Vec2i,
 }
 
+/// Points are iterated in the same order every run,
+/// so seeded simulations schedule pin updates the same way after loading
+pub type WirePoints = HashMap<Vec2i, WirePoint, BuildHasherDefault<DefaultHasher>>;
+
 #[derive(Debug, Default)]
 pub struct Wire {
     pub id: usize,
-    pub points: HashMap<Vec2i, WirePoint>,
+    pub points: WirePoints,
 }
 
 impl Wire {